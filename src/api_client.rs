@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use actix_web::http::Method;
+use chrono::Utc;
+use config::Config;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+type HmacSha256 = Hmac<Sha256>;
+
+use crate::api_signature_window;
+
+/// Права клиента API
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Чтение закрытых таблиц (outbox)
+    ReadOutbox,
+    /// Загрузка и изменение открытых таблиц (inbox)
+    WriteInbox,
+    /// Загрузка справочников учеников и учителей
+    Directories,
+    /// Всё, включая удаление, восстановление и повторное открытие закрытых таблиц
+    Admin,
+}
+
+impl ApiScope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiScope::ReadOutbox => "read_outbox",
+            ApiScope::WriteInbox => "write_inbox",
+            ApiScope::Directories => "directories",
+            ApiScope::Admin => "admin",
+        }
+    }
+}
+
+/// Клиент API, секция [[api.clients]] в teachserv.toml:
+/// id, secret_sha256 (sha256 секрета в hex, для Basic), scopes и, для подписанных запросов,
+/// signing_secret или signing_secret_env (имя переменной окружения с ним)
+#[derive(Debug, Deserialize)]
+pub struct ApiClient {
+    pub id: String,
+    secret_sha256: String,
+    #[serde(default)]
+    scopes: Vec<ApiScope>,
+    // the key of HMAC signatures: it is sensitive, unlike secret_sha256
+    #[serde(default)]
+    signing_secret: Option<String>,
+    #[serde(default)]
+    signing_secret_env: Option<String>,
+}
+
+impl ApiClient {
+    /// Клиенты из настроек; прежние api.login/api.password дают одного клиента с правами admin.
+    pub fn from_settings(settings: &Config) -> Vec<ApiClient> {
+        let mut clients: Vec<ApiClient> = settings.get("api.clients").unwrap_or_default();
+        for client in &mut clients {
+            assert!(
+                hex::decode(&client.secret_sha256).is_ok_and(|hash| hash.len() == 32),
+                "wrong api.clients secret_sha256 of {}", client.id
+            );
+            if let Some(var) = &client.signing_secret_env {
+                let secret = std::env::var(var).unwrap_or_else(|_| panic!("no {var} for signing_secret_env of {}", client.id));
+                client.signing_secret = Some(secret);
+            }
+            assert!(
+                client.signing_secret.as_ref().is_none_or(|secret| !secret.is_empty()),
+                "empty signing_secret of {}", client.id
+            );
+        }
+        if let (Ok(login), Ok(password)) = (settings.get_string("api.login"), settings.get_string("api.password")) {
+            clients.push(ApiClient {
+                id: login,
+                secret_sha256: sha256::digest(password.as_bytes()),
+                scopes: vec![ApiScope::Admin],
+                signing_secret: None,
+                signing_secret_env: None,
+            });
+        }
+        assert!(!clients.is_empty(), "neither api.clients nor api.login/api.password defined");
+        clients
+    }
+
+    // Constant-time comparison of the secret hashes
+    fn check_secret(&self, secret: &str) -> bool {
+        let given = Sha256::digest(secret.as_bytes());
+        let stored = hex::decode(&self.secret_sha256).unwrap_or_default();
+        given.as_slice().ct_eq(stored.as_slice()).into()
+    }
+
+    // Key of the signed requests; clients without it can use only Basic
+    fn signing_key(&self) -> Option<&[u8]> {
+        self.signing_secret.as_deref().map(str::as_bytes)
+    }
+
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&ApiScope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+pub fn authenticate<'a>(clients: &'a [ApiClient], id: &str, secret: &str) -> Option<&'a ApiClient> {
+    // the secret is checked for every client, so the time does not depend on the id
+    clients
+        .iter()
+        .map(|client| (client, client.id == id, client.check_secret(secret)))
+        .fold(None, |found, (client, id_ok, secret_ok)| found.or((id_ok && secret_ok).then_some(client)))
+}
+
+/// Заголовок подписанного запроса:
+/// Authorization: HMAC-SHA256 client=<id>, timestamp=<unix>, nonce=<строка>, signature=<hex>
+#[derive(Debug)]
+pub struct SignedAuth {
+    client: String,
+    timestamp: i64,
+    nonce: String,
+    signature: String,
+}
+
+impl SignedAuth {
+    pub fn parse(header: &str) -> Option<SignedAuth> {
+        let params = header.strip_prefix("HMAC-SHA256 ")?;
+        let param = |name: &str| {
+            params
+                .split(',')
+                .filter_map(|item| item.trim().split_once('='))
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.trim_matches('"').to_string())
+        };
+        Some(SignedAuth {
+            client: param("client")?,
+            timestamp: param("timestamp")?.parse().ok()?,
+            nonce: param("nonce")?,
+            signature: param("signature")?,
+        })
+    }
+}
+
+// Nonces seen within the window: "client nonce" -> timestamp
+lazy_static::lazy_static! {
+    static ref nonces: Mutex<HashMap<String, i64>> = Mutex::new(HashMap::new());
+}
+
+/// Строка для подписи: METHOD\nPATH?QUERY\nTIMESTAMP\nNONCE\nSHA256(тела) в hex
+pub fn string_to_sign(method: &str, path_and_query: &str, timestamp: i64, nonce: &str, body: &[u8]) -> String {
+    format!("{method}\n{path_and_query}\n{timestamp}\n{nonce}\n{}", sha256::digest(body))
+}
+
+/// Проверяет подписанный запрос: клиент, подпись, окно времени и повтор nonce.
+pub fn verify_signed<'a>(
+    clients: &'a [ApiClient],
+    auth: &SignedAuth,
+    method: &str,
+    path_and_query: &str,
+    body: &[u8],
+) -> Result<&'a ApiClient, String> {
+    let client =
+        clients
+            .iter()
+            .find(|client| client.id == auth.client)
+            .ok_or_else(|| format!("Unknown client {}", auth.client))?;
+
+    let key = client.signing_key().ok_or_else(|| format!("Client {} has no signing secret", client.id))?;
+
+    let now = Utc::now().timestamp();
+    if (now - auth.timestamp).abs() > *api_signature_window {
+        return Err(format!("Timestamp {} is outside of the window", auth.timestamp))
+    }
+
+    let signature = hex::decode(&auth.signature).map_err(|_| "Signature is not hex".to_string())?;
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(string_to_sign(method, path_and_query, auth.timestamp, &auth.nonce, body).as_bytes());
+    // constant-time comparison
+    mac.verify_slice(&signature).map_err(|_| "Wrong signature".to_string())?;
+
+    let mut nonces_guard = nonces.lock().unwrap_or_else(|e| e.into_inner());
+    nonces_guard.retain(|_, timestamp| (now - *timestamp).abs() <= *api_signature_window);
+    if nonces_guard.insert(format!("{} {}", client.id, auth.nonce), auth.timestamp).is_some() {
+        return Err(format!("Nonce {} is already used", auth.nonce))
+    }
+    Ok(client)
+}
+
+/// Права, нужные для запроса к API; None — достаточно любого клиента
+pub fn required_scope(method: &Method, path: &str) -> Option<ApiScope> {
+    // path without /api and /api/vN
+    let path = path.trim_start_matches("/api");
+    let path =
+        path.strip_prefix("/v1")
+            .or_else(|| path.strip_prefix("/v2"))
+            .unwrap_or(path);
+    let outbox =
+        path.starts_with("/attendances/outbox") ||
+            path.starts_with("/attendance/outbox") ||
+            path.starts_with("/tables/outbox");
+
+    if path == "/events" || path == "/openapi.json" {
+        None
+    } else if path.starts_with("/students") || path.starts_with("/teachers") {
+        Some(ApiScope::Directories)
+    } else if path.ends_with("/reopen") || (outbox && (method == Method::DELETE || path.ends_with("/restore"))) {
+        // changes of sealed tables
+        Some(ApiScope::Admin)
+    } else if outbox && method == Method::GET {
+        Some(ApiScope::ReadOutbox)
+    } else {
+        Some(ApiScope::WriteInbox)
+    }
+}
@@ -1,79 +1,203 @@
 use std::fs;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io;
 use chrono::{Datelike, NaiveDate, Weekday};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
 use actix_web::web;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tera::{Context, Tera};
 
-#[derive(Clone, Debug, Serialize)]
+use crate::mark::Mark;
+use crate::mark_codes;
+
+// Student's name and marks by date_range() index
+pub type StudentRow = (String, Vec<Option<Mark>>);
+
+// Tables are read, changed and written back under this lock,
+// by the teacher's save as well as by the API
+lazy_static::lazy_static! {
+    static ref tables_lock: Mutex<()> = Mutex::new(());
+}
+
+pub fn lock_tables() -> MutexGuard<'static, ()> {
+    tables_lock.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// JSON representation is the same as the TSV one (see Readme.md);
+// id and open are derived from the file location and are not accepted
+#[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
 pub struct Attendance {
+    #[serde(skip_deserializing)]
     id: String,
+    #[serde(skip_deserializing)]
     open: bool,
-    th_id:  i32,
-    th_name: String,
-    ss_id: i32,
-    ss_name: String,
-    date_min: NaiveDate,
-    date_max: NaiveDate,
-    date_filled: Option<NaiveDate>,
-    pub students: HashMap<i32, (String, Vec<String>)>
+    pub th_id:  i32,
+    pub th_name: String,
+    pub ss_id: i32,
+    pub ss_name: String,
+    pub date_min: NaiveDate,
+    pub date_max: NaiveDate,
+    #[serde(default)]
+    pub date_filled: Option<NaiveDate>,
+    // Number of blank rows for new students; the global blank_rows setting if not given
+    #[serde(default)]
+    blank_rows: Option<usize>,
+    // Teacher's free-text note to the whole table
+    #[serde(default)]
+    pub note: Option<String>,
+    // Corrections requested by reopening of the sealed table, with the reason (may be empty)
+    #[serde(default)]
+    pub correction: Option<String>,
+    // Topics of lessons by date
+    #[serde(default)]
+    pub topics: BTreeMap<NaiveDate, String>,
+    // [name, [marks...]] in JSON
+    #[schema(value_type = HashMap<i32, Vec<Object>>)]
+    pub students: HashMap<i32, StudentRow>,
+    // Teacher's comments to cells: student id -> date -> text
+    #[serde(default)]
+    pub comments: HashMap<i32, BTreeMap<NaiveDate, String>>,
+    // Students who left the group: student id -> last date in the group
+    #[serde(default)]
+    pub left: HashMap<i32, NaiveDate>
 }
 
 impl Attendance {
     pub fn read(tsv_file: &str) -> io::Result<Attendance> {
-        let file = File::open(tsv_file)?;
-        let reader = BufReader::new(file);
+        let text = fs::read_to_string(tsv_file)?;
+        let id = Path::new(tsv_file).file_stem().unwrap().to_str().unwrap();
+        Attendance::parse(id, tsv_file.contains("/inbox/"), &text)
+    }
+
+    pub fn parse(id: &str, open: bool, text: &str) -> io::Result<Attendance> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
 
-        let mut students: HashMap<i32, (String, Vec<String>)> = HashMap::new();
+        let mut students: HashMap<i32, StudentRow> = HashMap::new();
         let mut parameters: HashMap<String, String> = HashMap::new();
+        let mut comments: Vec<&str> = Vec::new();
+        let mut topics: Vec<&str> = Vec::new();
+        let mut left: Vec<&str> = Vec::new();
 
-        for line_result in reader.lines() {
-            line_result?
-                .split_once('\t')
-                .iter()
-                .for_each(|(key, value)| {
-                    match key.parse::<i32>() {
-                        Ok(st_id) => {
-                            let (st_name, attendance_table) =
-                                value
-                                    .split_once('\t')
-                                    .map_or(
-                                        (value.to_string(), Vec::new()),
-                                        |(value, tail)|
-                                                (
-                                                    value.to_string(),
-                                                    tail
-                                                        .split('\t')
-                                                        .map(|s| s.to_string())
-                                                        .collect()
-                                                )
-                                    );
-                            students.insert(st_id, (st_name, attendance_table));
-                        },
-                        Err(_) => {
-                            parameters.insert(key.to_string(), value.to_string());
-                        }
-                    }
-                });
+        for (key, value) in text.lines().filter_map(|line| line.split_once('\t')) {
+            match key {
+                "comment" => { comments.push(value); continue; },
+                "topic" => { topics.push(value); continue; },
+                "left" => { left.push(value); continue; },
+                _ => {}
+            }
+            match key.parse::<i32>() {
+                Ok(st_id) => {
+                    let (st_name, attendance_table) =
+                        value
+                            .split_once('\t')
+                            .map_or(
+                                Ok((value.to_string(), Vec::new())),
+                                |(value, tail)|
+                                    tail
+                                        .split('\t')
+                                        .map(Mark::parse)
+                                        .collect::<Result<Vec<_>, _>>()
+                                        .map(|mut marks| {
+                                            // write() pads rows with empty cells
+                                            trim_marks(&mut marks);
+                                            (value.to_string(), marks)
+                                        })
+                            )
+                            .map_err(|e| invalid(format!("{e} (student {st_id})")))?;
+                    students.insert(st_id, (st_name, attendance_table));
+                },
+                Err(_) => {
+                    parameters.insert(key.to_string(), value.to_string());
+                }
+            }
         }
 
         let format_str = "%Y-%m-%d";
+        let param = |key: &str| {
+            parameters.get(key).ok_or_else(|| invalid(format!("No {key}!")))
+        };
+        let date = |key: &str, s: &str| {
+            NaiveDate::parse_from_str(s, format_str)
+                .map_err(|e| invalid(format!("Cannot parse {key}: {e}")))
+        };
+
+        // comment<TAB>st_id<TAB>date<TAB>text
+        let mut comments_map: HashMap<i32, BTreeMap<NaiveDate, String>> = HashMap::new();
+        for value in comments {
+            let mut fields = value.splitn(3, '\t');
+            let (Some(st_id), Some(d), Some(text)) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(invalid(format!("Wrong comment: {value}")));
+            };
+            let st_id: i32 = st_id.parse().map_err(|_| invalid(format!("Wrong comment: {value}")))?;
+            comments_map
+                .entry(st_id)
+                .or_default()
+                .insert(date("comment", d)?, text.to_string());
+        }
 
-        let attendance = Attendance {
-            id: Path::new(tsv_file).file_stem().unwrap().to_str().unwrap().to_string(),
-            open: tsv_file.contains("/inbox/"),
-            th_id: parameters.get("th_id").expect("No th_id!").parse().expect("Cannot parse th_id"),
-            th_name: parameters.get("th_name").expect("No th_name!").to_string(),
-            ss_id: parameters.get("ss_id").expect("No ss_id!").parse().expect("Cannot parse ss_id"),
-            ss_name: parameters.get("ss_name").expect("No ss_name!").to_string(),
-            date_min: NaiveDate::parse_from_str(parameters.get("date_min").expect("No date_min!"), format_str).expect("Cannot parse date_min"),
-            date_max: NaiveDate::parse_from_str(parameters.get("date_max").expect("No date_max!"), format_str).expect("Cannot parse date_max"),
-            date_filled: parameters.get("date_filled").map(|d| NaiveDate ::parse_from_str(d, format_str).expect("Cannot parse date_filled")),
-            students: students
+        // topic<TAB>date<TAB>text
+        let topics =
+            topics
+                .into_iter()
+                .map(|value| {
+                    let (d, text) = value.split_once('\t').ok_or_else(|| invalid(format!("Wrong topic: {value}")))?;
+                    Ok((date("topic", d)?, text.to_string()))
+                })
+                .collect::<io::Result<_>>()?;
+
+        // left<TAB>st_id<TAB>date
+        let left =
+            left
+                .into_iter()
+                .map(|value| {
+                    let (st_id, d) = value.split_once('\t').ok_or_else(|| invalid(format!("Wrong left: {value}")))?;
+                    let st_id: i32 = st_id.parse().map_err(|_| invalid(format!("Wrong left: {value}")))?;
+                    Ok((st_id, date("left", d)?))
+                })
+                .collect::<io::Result<_>>()?;
+
+        let mut attendance = Attendance {
+            id: id.to_string(),
+            open,
+            th_id: param("th_id")?.parse().map_err(|_| invalid("Cannot parse th_id".to_string()))?,
+            th_name: param("th_name")?.to_string(),
+            ss_id: param("ss_id")?.parse().map_err(|_| invalid("Cannot parse ss_id".to_string()))?,
+            ss_name: param("ss_name")?.to_string(),
+            date_min: date("date_min", param("date_min")?)?,
+            date_max: date("date_max", param("date_max")?)?,
+            date_filled: parameters.get("date_filled").map(|d| date("date_filled", d)).transpose()?,
+            blank_rows: parameters.get("blank_rows").map(|n| n.parse()).transpose().map_err(|_| invalid("Cannot parse blank_rows".to_string()))?,
+            note: parameters.get("note").filter(|s| !s.is_empty()).cloned(),
+            correction: parameters.get("correction").cloned(),
+            topics,
+            students,
+            comments: comments_map,
+            left
         };
+
+        // dates<TAB>date<TAB>date...: dates of the mark columns; without it the columns
+        // go one by one from date_min (files written before the line was added)
+        if let Some(dates) = parameters.get("dates") {
+            let dates =
+                dates
+                    .split('\t')
+                    .map(|d| date("dates", d))
+                    .collect::<io::Result<Vec<_>>>()?;
+            let range = attendance.date_range();
+            for (st_id, (_, marks)) in attendance.students.iter_mut() {
+                let mut by_range = vec![None; range.len()];
+                for (i, mark) in marks.iter().enumerate().filter(|(_, mark)| mark.is_some()) {
+                    let i = dates
+                        .get(i)
+                        .and_then(|d| range.iter().position(|r| r == d))
+                        .ok_or_else(|| invalid(format!("Mark out of the dates (student {st_id})")))?;
+                    by_range[i] = *mark;
+                }
+                trim_marks(&mut by_range);
+                *marks = by_range;
+            }
+        }
         Ok(attendance)
     }
 
@@ -87,14 +211,41 @@ impl Attendance {
         bak_path_buf.set_file_name(new_file_name);
 
         // Rename (move) the file
-        fs::rename(&original_path, &bak_path_buf).ok()?;
+        fs::rename(original_path, &bak_path_buf).ok()?;
 
         Some(bak_path_buf)
     }
 
-    pub fn write(&self, tsv_file: &str) {
-        println!("Writing attendance file to {}", tsv_file);
+    // Table received as JSON: check what TSV parsing guarantees
+    pub fn from_json(id: &str, json: &str) -> Result<Attendance, String> {
+        let mut attendance: Attendance = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        attendance.id = id.to_string();
+        attendance.open = true;
+
+        if attendance.date_min > attendance.date_max {
+            return Err("date_min is after date_max".to_string())
+        }
+        let cols = attendance.date_range().len();
+        let texts =
+            [&attendance.th_name, &attendance.ss_name]
+                .into_iter()
+                .chain(attendance.note.iter())
+                .chain(attendance.correction.iter())
+                .chain(attendance.topics.values())
+                .chain(attendance.comments.values().flat_map(|comments| comments.values()))
+                .chain(attendance.students.values().map(|(name, _)| name));
+        for text in texts {
+            if text.contains(['\t', '\r', '\n']) {
+                return Err(format!("Tabs and line breaks are not allowed: {text:?}"))
+            }
+        }
+        if let Some((st_id, _)) = attendance.students.iter().find(|(_, (_, marks))| marks.len() > cols) {
+            return Err(format!("Too many marks for student {st_id}"))
+        }
+        Ok(attendance)
+    }
 
+    pub fn to_tsv(&self) -> String {
         let mut lines: Vec<String> = Vec::new();
         lines.push(format!("th_id\t{}", self.th_id));
         lines.push(format!("th_name\t{}", self.th_name));
@@ -102,11 +253,28 @@ impl Attendance {
         lines.push(format!("ss_name\t{}", self.ss_name));
         lines.push(format!("date_min\t{}", self.date_min));
         lines.push(format!("date_max\t{}", self.date_max));
+        lines.push(format!(
+            "dates\t{}",
+            self.date_range().iter().map(|d| d.to_string()).collect::<Vec<_>>().join("\t")
+        ));
         self.date_filled.iter().for_each(|date_filled|
             lines.push(format!("date_filled\t{}", date_filled))
         );
+        self.blank_rows.iter().for_each(|count|
+            lines.push(format!("blank_rows\t{}", count))
+        );
+        self.note.iter().for_each(|note|
+            lines.push(format!("note\t{}", note))
+        );
+        self.correction.iter().for_each(|reason|
+            lines.push(format!("correction\t{}", reason))
+        );
+        self.topics
+            .iter()
+            .filter(|(_, text)| !text.is_empty())
+            .for_each(|(d, text)| lines.push(format!("topic\t{d}\t{text}")));
 
-        let mut rows: Vec<(&i32, &(String, Vec<String>))> =
+        let mut rows: Vec<(&i32, &StudentRow)> =
             self.students
                 .iter()
                 .filter(|(_, (name, _))| !name.is_empty())
@@ -128,13 +296,34 @@ impl Attendance {
                 let data =
                     (0..cols+1)
                         .map(|i|
-                            data.get(i).map_or(String::new(), |s| s.to_string())
+                            data.get(i).copied().flatten().map_or(String::new(), |m| m.to_string())
                         )
                         .collect::<Vec<_>>()
                         .join("\t");
                 lines.push(format!("{st_id}\t{st_name}\t{data}"));
             });
 
+        rows
+            .iter()
+            .filter_map(|(st_id, _)| Some((st_id, self.left.get(st_id)?)))
+            .for_each(|(st_id, d)| lines.push(format!("left\t{st_id}\t{d}")));
+
+        rows
+            .iter()
+            .filter_map(|(st_id, _)| Some((st_id, self.comments.get(st_id)?)))
+            .for_each(|(st_id, comments)|
+                comments
+                    .iter()
+                    .filter(|(_, text)| !text.is_empty())
+                    .for_each(|(d, text)| lines.push(format!("comment\t{st_id}\t{d}\t{text}")))
+            );
+
+        lines.join("\n")
+    }
+
+    pub fn write(&self, tsv_file: &str) {
+        println!("Writing attendance file to {}", tsv_file);
+
         Attendance::move_to_bak(tsv_file);
 
         /*
@@ -145,8 +334,8 @@ impl Attendance {
             writeln!(file, "{}", line)?; // Write the line followed by a newline
         }
         */
-        fs::write(&tsv_file, &lines.join("\n"))
-            .expect(format!("Cannot write into file {tsv_file}").as_str());
+        fs::write(tsv_file, self.to_tsv())
+            .unwrap_or_else(|_| panic!("Cannot write into file {tsv_file}"));
     }
 
     pub fn date_range(&self) -> Vec<NaiveDate> {
@@ -159,24 +348,103 @@ impl Attendance {
         dates
     }
 
-    pub fn attendance_row(&self, st_id: i32) -> Vec<(NaiveDate, i32)> {
-        let (_, v) =
+    // Mark of the row on the date, None outside of the table's dates
+    fn mark_on(&self, marks: &[Option<Mark>], date: NaiveDate) -> Option<Mark> {
+        let i = usize::try_from((date - self.date_min).num_days()).ok()?;
+        marks.get(i).copied().flatten()
+    }
+
+    /// Есть ли в таблице что-то, внесённое учителем: отметки, темы, комментарии,
+    /// выбывшие, добавленные ученики, примечание или дата заполнения.
+    pub fn has_teacher_edits(&self) -> bool {
+        self.date_filled.is_some() ||
+            self.note.as_ref().is_some_and(|note| !note.is_empty()) ||
+            !self.topics.is_empty() ||
+            !self.comments.is_empty() ||
+            !self.left.is_empty() ||
             self.students
-                .get(&st_id)
-                .expect(format!("No student with id {st_id}!").as_str());
+                .iter()
+                .any(|(st_id, (_, marks))| *st_id < 0 || marks.iter().any(Option::is_some))
+    }
 
-        self
-            .date_range()
-            .iter()
-            .enumerate()
-            .map(|(idx, &date)|
-                (date, v.get(idx).map_or(0, |s| s.parse().unwrap_or(0)))
-            )
-            .collect()
+    /// Объединяет таблицу с загруженной заново: отметки, темы, комментарии и имена
+    /// остаются прежними, новые ученики и даты берутся из загруженной.
+    pub fn merge(&self, uploaded: &Attendance) -> Attendance {
+        let mut merged = uploaded.clone();
+        merged.date_min = self.date_min.min(uploaded.date_min);
+        merged.date_max = self.date_max.max(uploaded.date_max);
+        merged.date_filled = self.date_filled.or(uploaded.date_filled);
+        merged.blank_rows = self.blank_rows.or(uploaded.blank_rows);
+        merged.note = self.note.clone().or(uploaded.note.clone());
+        merged.correction = self.correction.clone().or(uploaded.correction.clone());
+        merged.topics.extend(self.topics.clone());
+        merged.left.extend(self.left.clone());
+        for (st_id, comments) in &self.comments {
+            merged.comments.entry(*st_id).or_default().extend(comments.clone());
+        }
+
+        let dates = merged.date_range();
+        let no_marks = Vec::new();
+        let st_ids: Vec<i32> = self.students.keys().chain(uploaded.students.keys()).copied().collect();
+        for st_id in st_ids {
+            let old = self.students.get(&st_id);
+            let new = uploaded.students.get(&st_id);
+            let name = old.or(new).map(|(name, _)| name.clone()).unwrap_or_default();
+            let old_marks = old.map_or(&no_marks, |(_, marks)| marks);
+            let new_marks = new.map_or(&no_marks, |(_, marks)| marks);
+            let mut marks: Vec<Option<Mark>> =
+                dates
+                    .iter()
+                    .map(|d| self.mark_on(old_marks, *d).or(uploaded.mark_on(new_marks, *d)))
+                    .collect();
+            trim_marks(&mut marks);
+            merged.students.insert(st_id, (name, marks));
+        }
+        merged
+    }
+
+    /// Меняет диапазон дат таблицы. Отметки переносятся по датам, а не по номерам
+    /// столбцов; если отметки, темы или комментарии выпадают из диапазона — ошибка.
+    pub fn set_dates(&mut self, date_min: NaiveDate, date_max: NaiveDate) -> Result<(), String> {
+        if date_min > date_max {
+            return Err("date_min is after date_max".to_string())
+        }
+        let outside = |d: &NaiveDate| *d < date_min || date_max < *d;
+        let old_range = self.date_range();
+        let marks_outside =
+            self.students
+                .values()
+                .flat_map(|(_, marks)| old_range.iter().zip(marks))
+                .filter(|(d, mark)| mark.is_some() && outside(d))
+                .count();
+        let texts_outside =
+            self.topics.keys()
+                .chain(self.comments.values().flat_map(|comments| comments.keys()))
+                .filter(|d| outside(d))
+                .count();
+        if marks_outside + texts_outside > 0 {
+            return Err(format!(
+                "{marks_outside} marks and {texts_outside} topics or comments are outside {date_min}..{date_max}"
+            ))
+        }
+
+        let mut changed = self.clone();
+        changed.date_min = date_min;
+        changed.date_max = date_max;
+        let range = changed.date_range();
+        for (st_id, (_, marks)) in changed.students.iter_mut() {
+            let old_marks = &self.students[st_id].1;
+            *marks = range.iter().map(|d| self.mark_on(old_marks, *d)).collect();
+            trim_marks(marks);
+        }
+        *self = changed;
+        Ok(())
     }
 
-    pub fn blank_range() -> Box<dyn Iterator<Item = i32>> {
-        Box::new((1..21).map(|i: i32| -i))
+    // At least one blank row is needed: the "add row" button clones the last one
+    pub fn blank_range(&self) -> impl Iterator<Item = i32> + use<> {
+        let count = i32::try_from(self.blank_rows.unwrap_or(*crate::blank_rows).max(1)).unwrap_or(i32::MAX);
+        (1..=count).map(|i: i32| -i)
     }
 
     pub fn html(&self, tera: &web::Data<Tera>, is_admin: bool) -> tera::Result<String> {
@@ -185,14 +453,34 @@ impl Attendance {
             .into_iter()
             .collect::<Vec<_>>();
 
-        let mut blanks: Vec<(i32, (String, Vec<String>))> =
-            Attendance::blank_range()
+        let mut blanks: Vec<(i32, StudentRow)> =
+            self.blank_range()
                 .map(|i: i32| (i, (String::new(), Vec::new())))
-                .collect(); // 20 empty lines
+                .collect();
 
         v.sort_by(|a, b| a.1.0.cmp(&b.1.0));
         v.append(&mut blanks);
 
+        let topics =
+            format!(
+                "<tr class=\"topics\">\
+                \t<td class=\"numcol\"></td>\n\
+                \t<td class=\"idcol\"></td>\n\
+                \t<td class=\"namecol\">Тема занятия</td>\n{}\n</tr>\n",
+                self.date_range()
+                    .iter()
+                    .map(|d| {
+                        let topic = self.topics.get(d).map_or(String::new(), |s| escape_html(s));
+                        format!(
+                            "\t<td title=\"{topic}\"><input \
+                                name=\"T{d}\" type=\"text\" class=\"topic\" \
+                                size=\"1\" value=\"{topic}\"></td>"
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+
         let table =
             format!(
                 "<thead>\n\
@@ -213,7 +501,8 @@ impl Attendance {
                     .join("\n")
             ) +
             format!(
-                "<tbody>\n{}\n</tbody>\n",
+                "<tbody>\n{}\n{}\n</tbody>\n",
+                topics,
                 v
                     .into_iter()
                     .enumerate()
@@ -221,36 +510,56 @@ impl Attendance {
                         format!(
                             "<tr>\
                             \t<td class=\"numcol\">{}</td>\n\
-                            \t<td class=\"idcol\"><input name=\"{}\" value=\"{}\"/></td>\n\
-                            \t<td class=\"namecol\">{}</td>\n{}\n</tr>\n",
+                            \t<td class=\"idcol\"><input name=\"IN{:05}\" value=\"{}\"/></td>\n\
+                            \t<td class=\"namecol{}\">{}</td>\n{}\n</tr>\n",
                             num + 1,
-                            format!("IN{id:05}"),
+                            id,
                             if id<0 { String::new() } else { format!("{id}") },
+                            if self.left.contains_key(&id) { " left" } else { "" },
                             if id<0 {
                                 let id = format!("N{id:05}");
                                 format!(
                                     "<input type=\"text\" name=\"{id}\" placeholder=\"новенький\" list=\"{id}\">\n\
                                     <datalist id=\"{id}\">\n</datalist>")
                             } else {
-                                name
+                                format!(
+                                    "{name}<input type=\"date\" name=\"L{id:05}\" class=\"left\" \
+                                        min=\"{}\" max=\"{}\" value=\"{}\" title=\"Выбыл из группы после даты\">",
+                                    self.date_min,
+                                    self.date_max,
+                                    self.left.get(&id).map_or(String::new(), |d| d.to_string())
+                                )
                             }
                             ,
                             self.date_range()
                                 .iter()
                                 .enumerate()
                                 .map(|(idx, d)| {
-                                    let weekend = match d.weekday() {
-                                        Weekday::Sat | Weekday::Sun => " class=\"weekend\"",
-                                        _ => ""
-                                    };
-                                    let default = "".to_string();
-                                    let v = v.get(idx).unwrap_or(&default);
+                                    let comment =
+                                        self.comments
+                                            .get(&id)
+                                            .and_then(|comments| comments.get(d))
+                                            .map_or(String::new(), |s| escape_html(s));
+                                    let left = self.left.get(&id).is_some_and(|left| d > left);
+                                    let classes = [
+                                        matches!(d.weekday(), Weekday::Sat | Weekday::Sun).then_some("weekend"),
+                                        (!comment.is_empty()).then_some("commented"),
+                                        left.then_some("left"),
+                                    ]
+                                        .into_iter()
+                                        .flatten()
+                                        .collect::<Vec<_>>()
+                                        .join(" ");
+                                    let class = if classes.is_empty() { classes } else { format!(" class=\"{classes}\"") };
+                                    let disabled = if left { " disabled" } else { "" };
+                                    let v = v.get(idx).copied().flatten().map_or(String::new(), |m| m.input_value());
                                     let v = format!(
                                         "<input \
-                                            name=\"S{id:05}D{d}\" type=\"number\" min=\"0\" \
-                                            size=\"1\" value=\"{v}\">"
+                                            name=\"S{id:05}D{d}\" type=\"text\" class=\"mark\" list=\"marks\" \
+                                            size=\"1\" value=\"{v}\"{disabled}>\
+                                        <input name=\"C{id:05}D{d}\" type=\"hidden\" value=\"{comment}\"{disabled}>"
                                     );
-                                    format!("\t<td{weekend}>{v}</td>")
+                                    format!("\t<td{class} title=\"{comment}\">{v}</td>")
                                 })
                                 .collect::<Vec<_>>()
                                 .join("\n")
@@ -264,7 +573,31 @@ impl Attendance {
         let mut context = Context::new();
         context.insert("is_admin", &is_admin);
         context.insert("attendance", self);
+        context.insert("marks", &mark_codes.legend());
         context.insert("table", table.as_str());
         tera.render("attendance.html", &context)
     }
-}
\ No newline at end of file
+}
+
+// Trailing empty cells are not kept
+fn trim_marks(marks: &mut Vec<Option<Mark>>) {
+    while marks.last().is_some_and(Option::is_none) {
+        marks.pop();
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Comments are stored in one TSV line
+pub fn clean_comment(s: &str) -> String {
+    s.split(['\t', '\r', '\n'])
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .to_string()
+}
@@ -0,0 +1,134 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::events_file;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Uploaded, // PUT or PATCH from the API
+    Saved,    // saved by the teacher
+    Sealed,   // moved to outbox by the teacher
+    Deleted,  // DELETE from the API, moved into the trash
+    Purged,   // removed as too old
+    Restored, // moved back from the trash by the API
+    Reopened, // moved back from outbox to inbox by the API
+}
+
+impl EventKind {
+    fn name(&self) -> &'static str {
+        match self {
+            EventKind::Uploaded => "uploaded",
+            EventKind::Saved => "saved",
+            EventKind::Sealed => "sealed",
+            EventKind::Deleted => "deleted",
+            EventKind::Purged => "purged",
+            EventKind::Restored => "restored",
+            EventKind::Reopened => "reopened",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<EventKind> {
+        [
+            EventKind::Uploaded,
+            EventKind::Saved,
+            EventKind::Sealed,
+            EventKind::Deleted,
+            EventKind::Purged,
+            EventKind::Restored,
+            EventKind::Reopened,
+        ]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct Event {
+    pub id: u64,
+    pub time: DateTime<Utc>,
+    pub kind: EventKind,
+    pub direction: String,
+    pub file: String,
+}
+
+impl Event {
+    // id<TAB>time<TAB>kind<TAB>direction<TAB>file
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}", self.id, self.time.to_rfc3339(), self.kind.name(), self.direction, self.file)
+    }
+
+    fn from_line(line: &str) -> Option<Event> {
+        let mut fields = line.split('\t');
+        Some(Event {
+            id: fields.next()?.parse().ok()?,
+            time: DateTime::parse_from_rfc3339(fields.next()?).ok()?.into(),
+            kind: EventKind::from_name(fields.next()?)?,
+            direction: fields.next()?.to_string(),
+            file: fields.next()?.to_string(),
+        })
+    }
+}
+
+// Event log, loaded from events_file at the first use
+lazy_static::lazy_static! {
+    static ref events: Mutex<Vec<Event>> = Mutex::new(load());
+}
+
+fn load() -> Vec<Event> {
+    fs::read_to_string(events_file.as_str())
+        .map(|text| text.lines().filter_map(Event::from_line).collect())
+        .unwrap_or_default()
+}
+
+pub fn record(kind: EventKind, direction: &str, file: &str) -> Event {
+    let mut events_guard = events.lock().unwrap_or_else(|e| e.into_inner());
+
+    let event = Event {
+        id: events_guard.last().map_or(1, |last| last.id + 1),
+        time: Utc::now(),
+        kind,
+        direction: direction.to_string(),
+        file: file.to_string(),
+    };
+    println!("Event: {}", event.to_line());
+
+    let written =
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(events_file.as_str())
+            .and_then(|mut f| writeln!(f, "{}", event.to_line()));
+    if let Err(e) = written {
+        println!("Cannot write event into {}: {e}", *events_file);
+    }
+
+    events_guard.push(event.clone());
+    event
+}
+
+// Events with id greater than the cursor, at most limit of them
+pub fn since(cursor: u64, limit: usize) -> Vec<Event> {
+    events
+        .lock()
+        .map(|events_guard|
+            events_guard
+                .iter()
+                .skip_while(|event| event.id <= cursor)
+                .take(limit)
+                .cloned()
+                .collect()
+        )
+        .unwrap_or_default()
+}
+
+pub fn last_id() -> u64 {
+    events
+        .lock()
+        .ok()
+        .and_then(|events_guard| events_guard.last().map(|event| event.id))
+        .unwrap_or(0)
+}
@@ -1,8 +1,11 @@
 use std::path::PathBuf;
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 
 #[derive(Debug, Serialize)]
 pub struct FileRec {
     pub file: PathBuf,
-    pub age: u64
+    pub age: u64,
+    pub size: u64,
+    pub modified: DateTime<Utc>
 }
\ No newline at end of file
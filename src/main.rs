@@ -1,6 +1,6 @@
 use std::ffi::OsStr;
 use std::fs;
-use std::io::{Error, ErrorKind, Result};
+use std::io::{Error, Result};
 
 use std::time::{SystemTime, SystemTimeError};
 use chrono::{Duration, NaiveDate};
@@ -10,20 +10,29 @@ use timer::Timer;
 use config::Config;
 
 use actix_web::{cookie::Key, App, HttpServer};
-use actix_web::web::{PayloadConfig, scope};
+use actix_web::web::{self, PayloadConfig};
+use utoipa_actix_web::AppExt;
 use actix_identity::IdentityMiddleware;
-use actix_session::{storage::RedisSessionStore, SessionMiddleware}; // unused
+use actix_session::SessionMiddleware;
 use actix_session::storage::CookieSessionStore;
-use actix_web_httpauth::middleware::HttpAuthentication;
 
 mod routes;
 mod teachrec;
 mod attendance;
 mod filerec;
 mod wrong_pwd;
+mod mark;
+mod events;
+mod webhook;
+mod api_client;
+mod tls;
+mod student_index;
 
-use routes::{index, student, teacher, api_tables};
+use routes::{index, student, teacher};
+use crate::events::EventKind;
 use crate::filerec::FileRec;
+use crate::mark::MarkCodes;
+use crate::api_client::ApiClient;
 
 lazy_static::lazy_static! {
     static ref settings: Config = Config::builder()
@@ -41,7 +50,7 @@ lazy_static::lazy_static! {
             .unwrap_or(b"very-secret-key-change-in-prod-please".to_vec());
 
     static ref valid_sec: i64 =
-        i64::try_from(settings.get_int("valid_sec").unwrap_or(60)).unwrap_or(60);
+        settings.get_int("valid_sec").unwrap_or(60);
 
     static ref cooldown_time: std::time::Duration =
         settings.get_string("cooldown_time")
@@ -56,46 +65,91 @@ lazy_static::lazy_static! {
     static ref max_table_age_days: u64 =
         u64::try_from(settings.get_int("max_table_age").unwrap_or(100)).unwrap_or(100);
 
+    // Default number of blank rows for new students in a table
+    static ref blank_rows: usize =
+        usize::try_from(settings.get_int("blank_rows").unwrap_or(20)).unwrap_or(20);
+
+    // HTTPS: PEM files of the certificate chain and the private key, see tls.rs
+    static ref tls_cert: Option<String> =
+        settings.get_string("tls.cert").ok();
+    static ref tls_key: Option<String> =
+        settings.get_string("tls.key").ok();
+    // Plain HTTP port redirecting to HTTPS
+    static ref tls_redirect_port: Option<u16> =
+        settings.get_int("tls.redirect_port").ok()
+            .map(|p| u16::try_from(p).expect("wrong tls.redirect_port value"));
+
+    // Maximum number of students found by the /students autocomplete
+    static ref students_limit: usize =
+        usize::try_from(settings.get_int("students_limit").unwrap_or(20)).unwrap_or(20);
+
     // Limit of PUT payload (size of table)
     static ref payload_limit: usize =
         usize::try_from(settings.get_int("payload_limit").unwrap_or(512*1024)).unwrap_or(512*1024);
 
-    static ref api_login: String =
-        settings.get_string("api.login").expect("api.login not defined");
-    static ref api_password: String =
-        settings.get_string("api.password").expect("api.password not defined");
+    // Limits of an archive of POST /api/attendance/batch after unpacking
+    static ref batch_max_files: usize =
+        usize::try_from(settings.get_int("batch.max_files").unwrap_or(1000)).unwrap_or(1000);
+    static ref batch_max_size: usize =
+        usize::try_from(settings.get_int("batch.max_size").unwrap_or(64*1024*1024)).unwrap_or(64*1024*1024);
+
+    // Clients of /api with their secrets and scopes, see api_client.rs
+    static ref api_clients: Vec<ApiClient> = ApiClient::from_settings(&settings);
+    // Allowed difference of the signed request timestamp from the server time, seconds
+    static ref api_signature_window: i64 =
+        settings.get_int("api.signature_window").unwrap_or(300);
+
+    // Log of changes of tables for GET /api/events
+    static ref events_file: String =
+        settings.get_string("events_file").unwrap_or("attendance/events.tsv".to_string());
+
+    // Notification about sealed tables, see webhook.rs
+    static ref webhook_url: Option<String> =
+        settings.get_string("webhook.url").ok();
+    static ref webhook_secret: Vec<u8> =
+        settings.get_string("webhook.secret").expect("webhook.secret not defined").into_bytes();
+    static ref webhook_queue: String =
+        settings.get_string("webhook.queue").unwrap_or("attendance/webhooks".to_string());
+    static ref webhook_max_attempts: u32 =
+        u32::try_from(settings.get_int("webhook.max_attempts").unwrap_or(20)).unwrap_or(20);
+
+    // Letter codes of marks: absent, sick etc.
+    static ref mark_codes: MarkCodes = MarkCodes::from_settings(&settings);
 }
 
 fn files_with_age(dir: &str) -> Result<Vec<FileRec>> {
     let now = SystemTime::now();
-    Ok(
-        fs::read_dir(dir)?
-            .map(|entry| {
-                let path = entry?.path();
-                let extension = path.extension();
-                let ok = path.is_file() && (
-                    extension == Some(OsStr::new("tsv")) || extension == Some(OsStr::new("bak"))
-                );
-
-                Ok(
-                    ok.then(|| {
-                        let metadata = fs::metadata(&path)?;
-                        let modified_time = metadata.modified()?;
-
-                        let elapsed_duration = now.duration_since(modified_time)
-                            .map_err(|e: SystemTimeError| {
-                                Error::new(ErrorKind::Other, format!("System time error: {}", e))
-                            })?; // Handle potential error if time is earlier than modification
-                        let days_ago: u64 = elapsed_duration.as_secs() / 3600 / 24;
-
-                        Ok(FileRec { file: path, age: days_ago })
+    fs::read_dir(dir)?
+        .map(|entry| {
+            let path = entry?.path();
+            let extension = path.extension();
+            let ok = path.is_file() && (
+                extension == Some(OsStr::new("tsv")) || extension == Some(OsStr::new("bak"))
+            );
+
+            Ok(
+                ok.then(|| {
+                    let metadata = fs::metadata(&path)?;
+                    let modified_time = metadata.modified()?;
+
+                    let elapsed_duration = now.duration_since(modified_time)
+                        .map_err(|e: SystemTimeError| {
+                            Error::other(format!("System time error: {}", e))
+                        })?; // Handle potential error if time is earlier than modification
+                    let days_ago: u64 = elapsed_duration.as_secs() / 3600 / 24;
+
+                    Ok(FileRec {
+                        file: path,
+                        age: days_ago,
+                        size: metadata.len(),
+                        modified: modified_time.into()
                     })
-                )
-            })
-            .map(|r: Result<_>| r?.transpose())
-            .filter_map(|r| r.transpose())
-            .collect::<Result<_>>()?
-    )
+                })
+            )
+        })
+        .map(|r: Result<_>| r?.transpose())
+        .filter_map(|r| r.transpose())
+        .collect::<Result<_>>()
 }
 
 fn rm_old_files(dir: &str) {
@@ -103,11 +157,18 @@ fn rm_old_files(dir: &str) {
         Err(e) =>
             println!("Error during timer attendance check: {}", e),
         Ok(v) =>
-            v.iter().for_each(|filerec::FileRec{ file: path, age}|
+            v.iter().for_each(|filerec::FileRec{ file: path, age, .. }|
                 if *age >= *max_table_age_days {
                     println!("Too old ({}): {}", age, path.display());
                     if let Err(e) = fs::remove_file(path) {
                         println!("Cannot delete file {}: {}!", path.display(), e);
+                    } else if path.extension() == Some(OsStr::new("tsv")) {
+                        // trash and revisions are not tables of the event log
+                        let direction = dir.trim_end_matches('/').trim_start_matches("attendance/");
+                        if direction == "inbox" || direction == "outbox" {
+                            let file = path.file_name().unwrap_or_default().to_string_lossy();
+                            events::record(EventKind::Purged, direction, &file);
+                        }
                     }
                 }
             ),
@@ -117,6 +178,10 @@ fn rm_old_files(dir: &str) {
 fn on_timer() {
     rm_old_files("attendance/inbox/");
     rm_old_files("attendance/outbox/");
+    rm_old_files("attendance/trash/inbox/");
+    rm_old_files("attendance/trash/outbox/");
+    rm_old_files("attendance/revisions/");
+    tls::reload_if_changed();
 }
 
 fn format_date_rus(value: &Value, _: &std::collections::HashMap<String, Value>) -> tera::Result<Value> {
@@ -129,6 +194,14 @@ async fn main() -> Result<()> {
     env_logger::init();
     log::info!("Application started.");
 
+    // check the settings here: a panic in the timer or webhook thread would stop it silently
+    if webhook_url.is_some() {
+        lazy_static::initialize(&webhook_secret);
+    }
+    // not at the first teacher page
+    lazy_static::initialize(&mark_codes);
+    webhook::start();
+
     // Start timer
     let timer = Timer::new();
     let _guard = timer.schedule_repeating(Duration::seconds(5), || {
@@ -144,27 +217,30 @@ async fn main() -> Result<()> {
 //        .await
 //        .unwrap();
 
+    student_index::reload();
+
     let mut tera = Tera::new("templates/**/*").unwrap();
     tera.autoescape_on(vec![]);
     tera.register_filter("fmt_date_rus", format_date_rus);
 
-    println!("teachserv: bind to {}:{}", *host, *port);
-    HttpServer::new(move || {
-        let api = scope("/api")
-            .wrap(HttpAuthentication::basic(routes::basic_auth_validator))
-            .service(api_tables::attendances)
-            .service(api_tables::get_attendance)
-            .service(api_tables::put_attendance)
-            .service(api_tables::put_attendance_no_hash)
-            .service(api_tables::delete_attendance)
-            .service(student::put_students)
-            .service(student::students_hash)
-            .service(student::put_teachers)
-            .service(student::teachers_hash);
-
-        App::new()
+    if let Some(redirect_port) = *tls_redirect_port {
+        // a redirect to the plain HTTP port would loop
+        assert!(tls::enabled(), "tls.redirect_port needs tls.cert and tls.key");
+        println!("teachserv: redirect to HTTPS from {}:{}", *host, redirect_port);
+        let redirect =
+            HttpServer::new(|| App::new().default_service(web::to(tls::redirect_to_https)))
+                .workers(1)
+                .bind(((*host).as_str(), redirect_port))?
+                .run();
+        actix_web::rt::spawn(redirect);
+    }
+
+    let tls_config = tls::server_config();
+    println!("teachserv: bind to {}:{}{}", *host, *port, if tls_config.is_some() { " (HTTPS)" } else { "" });
+    let server = HttpServer::new(move || {
+        let app = App::new()
             .app_data(PayloadConfig::new(*payload_limit))
-            .app_data(actix_web::web::Data::new(tera.to_owned()))
+            .app_data(web::Data::new(tera.to_owned()))
 
             // Install the identity framework first.
             // ??
@@ -182,7 +258,7 @@ async fn main() -> Result<()> {
             */
             .wrap(
                 SessionMiddleware::builder(CookieSessionStore::default(), Key::from(&[0; 64]))
-                    .cookie_secure(false)
+                    .cookie_secure(tls::enabled())
                     .build()
             )
             .service(index::index)
@@ -193,16 +269,20 @@ async fn main() -> Result<()> {
             .service(student::students)
             .service(teacher::table)
             .service(teacher::table_form)
-            .service(api)
             .service(
                 actix_files::Files::new("/static", "static")
                     // for debug:
                     //.index_file("index.html")
                     //.show_files_listing()
                     .use_last_modified(true)
-            )
-    })
-        .bind(((*host).as_str(), *port))?
+            );
+        let (app, api_doc) = routes::api_scopes(app.into_utoipa_app()).split_for_parts();
+        app.app_data(web::Data::new(api_doc))
+    });
+    match tls_config {
+        Some(tls_config) => server.bind_rustls_0_23(((*host).as_str(), *port), tls_config)?,
+        None => server.bind(((*host).as_str(), *port))?,
+    }
         .run()
         .await
 }
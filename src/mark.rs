@@ -0,0 +1,199 @@
+use std::fmt;
+use config::Config;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, Visitor};
+use utoipa::openapi::{ObjectBuilder, OneOfBuilder, RefOr, Schema, Type};
+
+use crate::mark_codes;
+
+/// Отметка в клетке таблицы посещаемости
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mark {
+    /// Присутствовал; число посещённых занятий за день
+    Present(u8),
+    /// Отсутствовал
+    Absent,
+    /// Отсутствовал по уважительной причине
+    Excused,
+    /// Болел
+    Sick,
+    /// Опоздал
+    Late,
+}
+
+// Codes of the marks in TSV and JSON; they do not depend on [marks], so the tables
+// stay readable when the settings change
+const STORED_CODES: [(Mark, &str); 4] = [
+    (Mark::Absent, "н"),
+    (Mark::Excused, "у"),
+    (Mark::Sick, "б"),
+    (Mark::Late, "о"),
+];
+
+fn stored(code: &str) -> Option<Mark> {
+    let code = code.to_lowercase();
+    STORED_CODES.iter().find(|(_, c)| *c == code).map(|(mark, _)| *mark)
+}
+
+impl Mark {
+    // Empty cell, number of lessons or a code found by find_code
+    fn parse_with(s: &str, find_code: impl Fn(&str) -> Option<Mark>) -> Result<Option<Mark>, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(None)
+        }
+        if let Ok(count) = s.parse::<u8>() {
+            return Ok(Some(Mark::Present(count)))
+        }
+        find_code(s)
+            .map(Some)
+            .ok_or_else(|| format!("Неизвестная отметка «{s}»"))
+    }
+
+    /// Разбирает клетку TSV или JSON. Пустая клетка даёт Ok(None).
+    /// Коды из [marks] тоже принимаются: клиенты API могут загружать таблицы с кодами учителя.
+    pub fn parse(s: &str) -> Result<Option<Mark>, String> {
+        Mark::parse_with(s, |code| stored(code).or_else(|| mark_codes.find(code)))
+    }
+
+    /// Разбирает отметку, введённую учителем: коды из [marks], затем постоянные.
+    pub fn parse_input(s: &str) -> Result<Option<Mark>, String> {
+        Mark::parse_with(s, |code| mark_codes.find(code).or_else(|| stored(code)))
+    }
+
+    /// Отметка для показа учителю, с кодом из [marks]
+    pub fn input_value(&self) -> String {
+        match self {
+            Mark::Present(count) => count.to_string(),
+            mark => mark_codes.code(*mark).unwrap_or_default().to_string(),
+        }
+    }
+}
+
+// As stored in TSV and JSON
+impl fmt::Display for Mark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mark::Present(count) => write!(f, "{count}"),
+            mark => {
+                let code = STORED_CODES.iter().find(|(m, _)| m == mark).map_or("", |(_, c)| c);
+                write!(f, "{code}")
+            }
+        }
+    }
+}
+
+// The count of lessons as a number, other marks as their stored code
+impl Serialize for Mark {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Mark::Present(n) => serializer.serialize_u8(*n),
+            mark => serializer.collect_str(mark),
+        }
+    }
+}
+
+// Accepts the same codes as a TSV cell; a number is the count of lessons
+impl<'de> Deserialize<'de> for Mark {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Mark, D::Error> {
+        struct MarkVisitor;
+
+        impl Visitor<'_> for MarkVisitor {
+            type Value = Mark;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number of lessons or a mark code")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Mark, E> {
+                u8::try_from(v)
+                    .map(Mark::Present)
+                    .map_err(|_| E::custom(format!("too big number of lessons: {v}")))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Mark, E> {
+                Mark::parse(v)
+                    .map_err(E::custom)?
+                    .ok_or_else(|| E::custom("empty mark, use null"))
+            }
+        }
+
+        deserializer.deserialize_any(MarkVisitor)
+    }
+}
+
+// In JSON a mark is a number of lessons or a mark code
+impl utoipa::PartialSchema for Mark {
+    fn schema() -> RefOr<Schema> {
+        OneOfBuilder::new()
+            .item(ObjectBuilder::new().schema_type(Type::Integer).minimum(Some(0)).maximum(Some(255)))
+            .item(ObjectBuilder::new().schema_type(Type::String).description(Some("mark code: н (absent), у (excused), б (sick), о (late)")))
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for Mark {}
+
+#[derive(Debug, Serialize)]
+pub struct MarkCode {
+    pub code: String,
+    pub title: String,
+}
+
+/// Буквенные коды отметок для учителя, секция [marks] в teachserv.toml:
+/// absent, excused, sick, late. В файлах таблиц — постоянные коды STORED_CODES.
+#[derive(Debug)]
+pub struct MarkCodes {
+    codes: Vec<(Mark, MarkCode)>,
+}
+
+impl MarkCodes {
+    pub fn from_settings(settings: &Config) -> MarkCodes {
+        let code = |mark: Mark, key: &str, title: &str| {
+            let default = STORED_CODES.iter().find(|(m, _)| *m == mark).map_or("", |(_, c)| c);
+            let code =
+                settings
+                    .get_string(format!("marks.{key}").as_str())
+                    .unwrap_or(default.to_string());
+            assert!(
+                !code.is_empty() && code.parse::<u8>().is_err(),
+                "wrong marks.{key} value: {code}"
+            );
+            (mark, MarkCode { code, title: title.to_string() })
+        };
+
+        let codes = vec![
+            code(Mark::Absent, "absent", "отсутствовал"),
+            code(Mark::Excused, "excused", "уважительная причина"),
+            code(Mark::Sick, "sick", "болел"),
+            code(Mark::Late, "late", "опоздал"),
+        ];
+        // the teacher's input is looked up by the code
+        for (i, (_, mark_code)) in codes.iter().enumerate() {
+            assert!(
+                codes[..i].iter().all(|(_, other)| other.code != mark_code.code),
+                "marks codes must differ: {} is used twice", mark_code.code
+            );
+        }
+        MarkCodes { codes }
+    }
+
+    pub fn find(&self, code: &str) -> Option<Mark> {
+        let code = code.to_lowercase();
+        self.codes
+            .iter()
+            .find(|(_, c)| c.code.to_lowercase() == code)
+            .map(|(mark, _)| *mark)
+    }
+
+    pub fn code(&self, mark: Mark) -> Option<&str> {
+        self.codes
+            .iter()
+            .find(|(m, _)| *m == mark)
+            .map(|(_, c)| c.code.as_str())
+    }
+
+    pub fn legend(&self) -> Vec<&MarkCode> {
+        self.codes.iter().map(|(_, c)| c).collect()
+    }
+}
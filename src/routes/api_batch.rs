@@ -0,0 +1,178 @@
+use actix_web::{get, post, HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use log::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::attendance::{self, Attendance};
+use crate::{batch_max_files, batch_max_size, payload_limit, routes};
+use crate::routes::api_error::{ApiError, ApiResult};
+use crate::routes::api_tables::{self, TablesFilter};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BatchQuery {
+    // "yes" - write nothing if any table is invalid
+    all_or_nothing: Option<String>,
+    // replace or merge tables already edited by the teacher, as in PUT
+    mode: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BatchRec {
+    file: String,
+    ok: bool,
+    error: Option<String>,
+}
+
+// File name and its contents or error
+type ArchiveFile = (String, Result<String, String>);
+
+// Unpacked entry of at most payload_limit bytes, like a single PUT;
+// the whole archive may unpack to at most batch.max_size bytes
+fn read_entry(entry: impl Read, total: &mut usize) -> Result<Result<String, String>, String> {
+    let mut contents = Vec::new();
+    if let Err(e) = entry.take(*payload_limit as u64 + 1).read_to_end(&mut contents) {
+        return Ok(Err(e.to_string()))
+    }
+    *total += contents.len();
+    if *total > *batch_max_size {
+        return Err(format!("More than {} bytes unpacked", *batch_max_size))
+    }
+    if contents.len() > *payload_limit {
+        return Ok(Err(format!("File is larger than {} bytes", *payload_limit)))
+    }
+    Ok(String::from_utf8(contents).map_err(|e| e.to_string()))
+}
+
+fn too_many_files() -> String {
+    format!("More than {} files", *batch_max_files)
+}
+
+// Every file of a zip or tar archive
+fn archive_files(body: &[u8]) -> Result<Vec<ArchiveFile>, String> {
+    let file_name = |path: &std::path::Path|
+        path.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_owned();
+    let mut files = Vec::new();
+    let mut total = 0;
+
+    if body.starts_with(b"PK") {
+        let mut archive = zip::ZipArchive::new(Cursor::new(body)).map_err(|e| e.to_string())?;
+        if archive.len() > *batch_max_files {
+            return Err(too_many_files())
+        }
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i).map_err(|e| e.to_string())?;
+            if entry.is_dir() {
+                continue
+            }
+            let name = entry.enclosed_name().map(|path| file_name(&path)).unwrap_or_default();
+            files.push((name, read_entry(entry, &mut total)?));
+        }
+    } else {
+        let mut archive = tar::Archive::new(Cursor::new(body));
+        for (i, entry) in archive.entries().map_err(|e| e.to_string())?.enumerate() {
+            if i >= *batch_max_files {
+                return Err(too_many_files())
+            }
+            let entry = entry.map_err(|e| e.to_string())?;
+            if !entry.header().entry_type().is_file() {
+                continue
+            }
+            let name = entry.path().map(|path| file_name(&path)).unwrap_or_default();
+            files.push((name, read_entry(entry, &mut total)?));
+        }
+    }
+    Ok(files)
+}
+
+// Zip or tar archive of TSV tables into the inbox
+#[utoipa::path(
+    tag = "tables",
+    params(BatchQuery),
+    request_body(content = Vec<u8>, description = "zip or tar archive of TSV tables", content_type = "application/zip"),
+    responses(
+        (status = 200, description = "Report by files", body = Vec<BatchRec>),
+        (status = 400, description = "Invalid archive or rejected batch", body = ApiError),
+    )
+)]
+#[post("/attendance/batch")] // /api
+pub async fn post_batch(
+    req: HttpRequest,
+    query: web::Query<BatchQuery>,
+    body: web::Bytes
+) -> ApiResult<HttpResponse> {
+    routes::check_digest(&req, &body)?;
+    let all_or_nothing = query.all_or_nothing.as_deref() == Some("yes");
+    let files =
+        archive_files(&body)
+            .map_err(|e| ApiError::bad_request("invalid_archive", format!("Invalid archive: {e}")))?;
+    if files.is_empty() {
+        return Err(ApiError::bad_request("invalid_archive", "Empty archive"))
+    }
+
+    let _lock = attendance::lock_tables();
+    let checked: Vec<ArchiveFile> =
+        files
+            .into_iter()
+            .map(|(file, contents)| {
+                let checked = contents.and_then(|text| {
+                    api_tables::check_file_name(&file).map_err(|e| e.to_string())?;
+                    Attendance::parse(&file, false, &text).map_err(|e| format!("Invalid table: {e}"))?;
+                    api_tables::overwrite(&file, text, query.mode.as_deref()).map_err(|e| e.to_string())
+                });
+                (file, checked)
+            })
+            .collect();
+    let failed = checked.iter().any(|(_, checked)| checked.is_err());
+
+    let mut report = Vec::new();
+    for (file, checked) in checked {
+        let result = match checked {
+            Ok(_) if failed && all_or_nothing => Err("Not written: other tables are invalid or conflicting".to_string()),
+            Ok(text) => api_tables::write_inbox(&file, &text).map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &result {
+            warn!("Batch upload, {file}: {e}");
+        }
+        report.push(BatchRec { file, ok: result.is_ok(), error: result.err() });
+    }
+
+    if failed && all_or_nothing {
+        return Err(ApiError::bad_request("batch_rejected", "No tables written: some are invalid").details(report))
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Sealed tables as a zip archive, filtered like /attendances/outbox/list
+#[utoipa::path(
+    tag = "tables",
+    params(TablesFilter),
+    responses(
+        (status = 200, description = "zip archive of sealed tables", body = Vec<u8>, content_type = "application/zip"),
+    )
+)]
+#[get("/attendance/outbox.zip")] // /api
+pub async fn get_outbox_zip(filter: web::Query<TablesFilter>) -> ApiResult<HttpResponse> {
+    let tables = api_tables::table_recs("outbox", &filter)?;
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options =
+        zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+    for rec in &tables {
+        let contents = fs::read(format!("attendance/outbox/{}", rec.file))?;
+        zip.start_file(rec.file.as_str(), options).map_err(std::io::Error::other)?;
+        zip.write_all(&contents)?;
+    }
+    let archive = zip.finish().map_err(std::io::Error::other)?.into_inner();
+    println!("outbox.zip: {} tables, {} bytes", tables.len(), archive.len());
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"outbox.zip\""))
+        .body(archive))
+}
@@ -0,0 +1,106 @@
+use actix_web::{get, web, HttpResponse};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::attendance::Attendance;
+use crate::events::{Event, EventKind};
+use crate::routes::{api_batch, api_events, api_students, api_tables, api_v2, student};
+use crate::routes::api_error::ApiError;
+
+/// Описание API (OpenAPI 3): общие сведения и схемы; пути добавляет routes::api_scopes
+/// из атрибутов utoipa::path обработчиков и их маршрутов actix
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "teachserv API",
+        description = "Таблицы посещаемости и справочники. /api — то же, что /api/v1."
+    ),
+    components(schemas(
+        Attendance, ApiError, Event, EventKind,
+        api_tables::TableRec, api_tables::TableChanges, api_tables::ReopenRequest,
+        api_batch::BatchRec, api_events::EventsPage, api_v2::PutResult,
+        student::Student, api_students::StudentName,
+    )),
+    modifiers(&ApiAuth),
+    security(("basic_auth" = []), ("hmac_signature" = [])),
+)]
+pub struct ApiDoc;
+
+struct ApiAuth;
+
+impl Modify for ApiAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "basic_auth",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+            );
+            components.add_security_scheme(
+                "hmac_signature",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "Authorization",
+                    "HMAC-SHA256 client=<id>, timestamp=<unix>, nonce=<nonce>, signature=<hex>",
+                ))),
+            );
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "docs",
+    responses((status = 200, description = "This document", content_type = "application/json"))
+)]
+#[get("/openapi.json")] // /api
+pub async fn openapi_json(api_doc: web::Data<utoipa::openapi::OpenApi>) -> HttpResponse {
+    HttpResponse::Ok().json(api_doc.get_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App, HttpResponse, web};
+    use actix_web::http::{Method, StatusCode};
+    use utoipa_actix_web::AppExt;
+
+    use crate::routes;
+
+    // Status of the App default service: no route for the request
+    const NO_ROUTE: StatusCode = StatusCode::IM_A_TEAPOT;
+
+    // Every operation of the generated description is served by the same App routes as in main
+    #[actix_web::test]
+    async fn every_documented_operation_is_routed() {
+        routes::tests::work_dir();
+        let (app, api_doc) =
+            routes::api_scopes(App::new().into_utoipa_app())
+                .map(|app| app.default_service(web::to(|| async { HttpResponse::build(NO_ROUTE).finish() })))
+                .split_for_parts();
+        let doc = serde_json::to_value(&api_doc).unwrap();
+        let app = test::init_service(app.app_data(web::Data::new(api_doc))).await;
+        let auth = ("Authorization", "Basic YXBpOnNlY3JldA=="); // api:secret
+
+        let request = |method: Method, path: &str| {
+            test::TestRequest::default().method(method).uri(path).insert_header(auth).to_request()
+        };
+        let res = test::call_service(&app, request(Method::GET, "/api/v1/no-such-route")).await;
+        assert_eq!(res.status(), NO_ROUTE, "the default service is not reached");
+
+        let paths = doc["paths"].as_object().unwrap();
+        let mut operations = 0;
+        for (path, item) in paths {
+            assert!(path.starts_with("/api/v1/") || path.starts_with("/api/v2/"), "{path} is not versioned");
+            // any value fits the path parameters: only the routing is checked
+            let uri: String =
+                path.split('/')
+                    .map(|part| if part.starts_with('{') { "1" } else { part })
+                    .collect::<Vec<_>>()
+                    .join("/");
+            for method in item.as_object().unwrap().keys().filter(|key| *key != "parameters") {
+                let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+                let res = test::call_service(&app, request(method.clone(), &uri)).await;
+                assert_ne!(res.status(), NO_ROUTE, "{method} {path} is documented but not routed");
+                operations += 1;
+            }
+        }
+        assert!(operations > 20, "too few operations documented: {operations}");
+    }
+}
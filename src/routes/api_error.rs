@@ -0,0 +1,83 @@
+use std::fmt;
+use std::io;
+use actix_web::{HttpResponse, ResponseError};
+use actix_web::http::{header, StatusCode};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+/// Ошибка API: JSON-тело {code, message, details}.
+/// code — постоянный машинный код, message — текст для оператора.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    #[serde(skip)]
+    #[schema(ignore)]
+    status: StatusCode,
+    #[schema(value_type = String, example = "not_found")]
+    pub code: &'static str,
+    pub message: String,
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError { status, code, message: message.into(), details: None }
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> ApiError {
+        ApiError::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError::new(StatusCode::CONFLICT, code, message)
+    }
+
+    pub fn details(mut self, details: impl Serialize) -> ApiError {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        log::warn!("API error {} {}: {}", self.status.as_u16(), self.code, self.message);
+        let mut response = HttpResponse::build(self.status);
+        if self.status == StatusCode::UNAUTHORIZED {
+            // the challenge for clients that send credentials only after it
+            response.insert_header((header::WWW_AUTHENTICATE, "Basic realm=\"teachserv API\""));
+        }
+        response.json(self)
+    }
+}
+
+// Errors of reading and writing the files
+impl From<io::Error> for ApiError {
+    fn from(e: io::Error) -> ApiError {
+        match e.kind() {
+            io::ErrorKind::NotFound => ApiError::not_found(e.to_string()),
+            io::ErrorKind::InvalidData => ApiError::bad_request("invalid_table", format!("Invalid table: {e}")),
+            _ => ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "io_error", e.to_string()),
+        }
+    }
+}
+
+// Errors of the extractors (query string, path) in the /api scope
+pub fn extractor_error(e: impl fmt::Display) -> actix_web::Error {
+    ApiError::bad_request("invalid_request", e.to_string()).into()
+}
@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+use actix_web::{get, web, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::events::{self, Event};
+use crate::routes::api_error::ApiError;
+
+// Longest wait for new events, seconds
+const MAX_WAIT: u64 = 60;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventsQuery {
+    // last event id already seen by the client
+    since: Option<u64>,
+    // long poll: seconds to wait when there are no new events
+    wait: Option<u64>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EventsPage {
+    events: Vec<Event>,
+    // value of since for the next call
+    cursor: u64,
+}
+
+#[utoipa::path(
+    tag = "events",
+    params(EventsQuery),
+    responses(
+        (status = 200, description = "Events after the cursor", body = EventsPage),
+        (status = 400, description = "Invalid query", body = ApiError),
+    )
+)]
+#[get("/events")] // /api
+pub async fn get_events(query: web::Query<EventsQuery>) -> impl Responder {
+    web::Json(events_page(&query).await)
+}
+
+// Events after query.since, waiting for them up to query.wait seconds
+pub async fn events_page(query: &EventsQuery) -> EventsPage {
+    let since = query.since.unwrap_or(0);
+    let limit = query.limit.unwrap_or(1000);
+    let deadline = Instant::now() + Duration::from_secs(query.wait.unwrap_or(0).min(MAX_WAIT));
+
+    loop {
+        let found = events::since(since, limit);
+        if !found.is_empty() || Instant::now() >= deadline {
+            // since beyond the log (e.g. the log was removed) resets the client
+            let cursor = found.last().map_or(since.min(events::last_id()), |event| event.id);
+            return EventsPage { events: found, cursor }
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+    }
+}
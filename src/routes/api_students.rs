@@ -0,0 +1,128 @@
+use actix_web::{delete, get, post, put, HttpRequest, HttpResponse, Responder, web};
+use actix_web::web::Path;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::routes;
+use crate::routes::api_error::{ApiError, ApiResult};
+use crate::routes::student::{self, Student, StudentRecords};
+
+// Students directory as JSON; every change rewrites students.tsv, see student::StudentRecords
+
+#[derive(Deserialize, ToSchema)]
+pub struct StudentName {
+    name: String,
+}
+
+fn no_student(id: i32) -> ApiError {
+    ApiError::not_found(format!("No student {id}"))
+}
+
+#[utoipa::path(
+    tag = "directories",
+    responses(
+        (status = 200, description = "All students by id", body = Vec<Student>),
+        (status = 404, description = "No file", body = ApiError),
+    )
+)]
+#[get("/students")] // /api
+pub async fn list_students() -> ApiResult<impl Responder> {
+    let mut students: Vec<Student> =
+        student::read_students()
+            .map_err(std::io::Error::from)?
+            .into_iter()
+            .map(|(id, name)| Student { id, name })
+            .collect();
+    students.sort_by_key(|student| student.id);
+    Ok(web::Json(students))
+}
+
+#[utoipa::path(
+    tag = "directories",
+    params(("id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Student", body = Student),
+        (status = 404, description = "No student", body = ApiError),
+    )
+)]
+#[get("/students/{id}")] // /api
+pub async fn get_student(id: Path<i32>) -> ApiResult<impl Responder> {
+    let id = id.into_inner();
+    let name =
+        student::read_students()
+            .map_err(std::io::Error::from)?
+            .remove(&id)
+            .ok_or_else(|| no_student(id))?;
+    Ok(web::Json(Student { id, name }))
+}
+
+#[utoipa::path(
+    tag = "directories",
+    request_body = Student,
+    responses(
+        (status = 201, description = "Added", body = Student),
+        (status = 400, description = "Invalid student", body = ApiError),
+        (status = 409, description = "Student with this id exists", body = ApiError),
+    )
+)]
+#[post("/students")] // /api
+pub async fn create_student(new_student: web::Json<Student>) -> ApiResult<HttpResponse> {
+    let new_student = new_student.into_inner();
+    let _lock = student::lock_students();
+    let mut records = StudentRecords::read()?;
+    if records.contains(new_student.id) {
+        return Err(ApiError::conflict("already_exists", format!("Student {} already exists", new_student.id)))
+    }
+    records.set(new_student.id, &new_student.name);
+    records.write()?;
+    println!("Student added: {} {}", new_student.id, new_student.name);
+    Ok(HttpResponse::Created().json(new_student))
+}
+
+#[utoipa::path(
+    tag = "directories",
+    params(("id" = i32, Path, description = "Student id")),
+    request_body = StudentName,
+    responses(
+        (status = 200, description = "Changed", body = Student),
+        (status = 400, description = "Invalid name or digest mismatch", body = ApiError),
+        (status = 404, description = "No student", body = ApiError),
+    )
+)]
+#[put("/students/{id}")] // /api
+pub async fn update_student(req: HttpRequest, id: Path<i32>, body: String) -> ApiResult<impl Responder> {
+    routes::check_digest(&req, body.as_bytes())?;
+    let body: StudentName =
+        serde_json::from_str(&body).map_err(|e| ApiError::bad_request("invalid_request", e.to_string()))?;
+    let id = id.into_inner();
+    let _lock = student::lock_students();
+    let mut records = StudentRecords::read()?;
+    if !records.contains(id) {
+        return Err(no_student(id))
+    }
+    records.set(id, &body.name);
+    records.write()?;
+    println!("Student changed: {id} {}", body.name);
+    Ok(web::Json(Student { id, name: body.name }))
+}
+
+#[utoipa::path(
+    tag = "directories",
+    params(("id" = i32, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Deleted"),
+        (status = 404, description = "No student", body = ApiError),
+    )
+)]
+#[delete("/students/{id}")] // /api
+pub async fn delete_student(id: Path<i32>) -> ApiResult<impl Responder> {
+    let id = id.into_inner();
+    let _lock = student::lock_students();
+    let mut records = StudentRecords::read()?;
+    if !records.remove(id) {
+        return Err(no_student(id))
+    }
+    records.write()?;
+    println!("Student deleted: {id}");
+    Ok(HttpResponse::Ok().body("OK"))
+}
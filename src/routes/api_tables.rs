@@ -1,8 +1,17 @@
-use actix_web::{get, put, delete, error, HttpResponse, Responder, web};
-use actix_web::web::{JsonBody, Path};
+use actix_web::{get, post, put, patch, delete, HttpMessage, HttpRequest, HttpResponse, Responder, web};
+use actix_web::http::{header, StatusCode};
+use actix_web::web::Path;
+use std::collections::BTreeMap;
 use std::fs;
+use chrono::{DateTime, NaiveDate, Utc};
 use log::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
+use crate::attendance::{self, Attendance, clean_comment};
+use crate::routes;
+use crate::routes::api_error::{ApiError, ApiResult};
+use crate::events::{self, EventKind};
 use crate::filerec::FileRec;
 use crate::files_with_age;
 
@@ -13,28 +22,57 @@ fn is_alphanumeric_underscore_dot(s: &str) -> bool {
         (dot_count == 1 || dot_count == 2)
 }
 
-fn check_file_name(file_name: &str) -> actix_web::Result<()> {
+pub fn check_file_name(file_name: &str) -> ApiResult<()> {
     if is_alphanumeric_underscore_dot(file_name) {
         Ok(())
     } else {
-        Err(error::ErrorBadRequest("Invalid data provided"))
+        Err(ApiError::bad_request("invalid_file_name", format!("Invalid file name: {file_name}")))
     }
 }
 
-#[get("/attendances/{direction}")] // /api
-pub async fn attendances(direction: Path<String>) -> actix_web::Result<impl Responder> {
-    if direction.as_str() != "inbox" && direction.as_str() != "outbox" {
-        let msg = format!("Wrong direction: {}!", direction.as_str());
+// Name of the n-th copy of a table in the trash: {file}, then {stem}~2.{ext}, {stem}~3.{ext}...
+// ('~' is not allowed in table names, so copies never clash with tables)
+fn trash_name(file: &str, n: u32) -> String {
+    match (n, file.rsplit_once('.')) {
+        (1, _) | (_, None) => file.to_string(),
+        (n, Some((stem, ext))) => format!("{stem}~{n}.{ext}"),
+    }
+}
+
+fn is_trash_copy(file: &str, name: &str) -> bool {
+    name == file ||
+        name.split_once('~').and_then(|(_, rest)| rest.split_once('.'))
+            .and_then(|(n, _)| n.parse::<u32>().ok())
+            .is_some_and(|n| trash_name(file, n) == name)
+}
+
+pub fn check_direction(direction: &str) -> ApiResult<()> {
+    if direction != "inbox" && direction != "outbox" {
+        let msg = format!("Wrong direction: {direction}!");
         log::error!("{msg}");
-        return Err(error::ErrorMethodNotAllowed(msg))
+        return Err(ApiError::new(StatusCode::METHOD_NOT_ALLOWED, "wrong_direction", msg))
     }
+    Ok(())
+}
+
+#[utoipa::path(
+    tag = "tables",
+    params(("direction" = String, Path, description = "inbox or outbox")),
+    responses(
+        (status = 200, description = "File names of the tables", body = Vec<String>),
+        (status = 405, description = "Wrong direction", body = ApiError),
+    )
+)]
+#[get("/attendances/{direction}")] // /api
+pub async fn attendances(direction: Path<String>) -> ApiResult<impl Responder> {
+    check_direction(direction.as_str())?;
 
     let folder = format!("attendance/{direction}");
     println!("mask={folder}");
     let files =
         files_with_age(folder.as_str())?
             .into_iter()
-            .filter_map(|r @ FileRec { file: _, age } | {
+            .filter_map(|r @ FileRec { .. } | {
                 let _ext = r.file.extension().filter(|&ext| ext == "tsv")?;
                 let file = r.file.file_name()?.to_str()?;
                 Some(file.to_owned())
@@ -44,39 +82,235 @@ pub async fn attendances(direction: Path<String>) -> actix_web::Result<impl Resp
     Ok(web::Json::<Vec<String>>(files))
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct TableRec {
+    pub file: String,
+    th_id: i32,
+    th_name: String,
+    ss_id: i32,
+    ss_name: String,
+    date_min: NaiveDate,
+    date_max: NaiveDate,
+    date_filled: Option<NaiveDate>,
+    students: usize,
+    age: u64,
+    size: u64,
+    modified: DateTime<Utc>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TablesFilter {
+    th_id: Option<i32>,
+    ss_id: Option<i32>,
+    // tables that intersect [date_from, date_to]
+    date_from: Option<NaiveDate>,
+    date_to: Option<NaiveDate>,
+    // tables written (sealed for outbox) after the moment, RFC 3339
+    sealed_after: Option<DateTime<Utc>>,
+}
+
+impl TablesFilter {
+    fn accepts(&self, rec: &TableRec) -> bool {
+        self.th_id.is_none_or(|th_id| th_id == rec.th_id) &&
+            self.ss_id.is_none_or(|ss_id| ss_id == rec.ss_id) &&
+            self.date_from.is_none_or(|d| d <= rec.date_max) &&
+            self.date_to.is_none_or(|d| rec.date_min <= d) &&
+            self.sealed_after.is_none_or(|t| rec.modified > t)
+    }
+}
+
+// Tables of the folder with their metadata, in the order of modification
+pub fn table_recs(direction: &str, filter: &TablesFilter) -> std::io::Result<Vec<TableRec>> {
+    let folder = format!("attendance/{direction}");
+    let mut tables =
+        files_with_age(folder.as_str())?
+            .into_iter()
+            .filter(|r| r.file.extension().is_some_and(|ext| ext == "tsv"))
+            .filter_map(|FileRec { file, age, size, modified }| {
+                let attendance =
+                    Attendance::read(file.to_str()?)
+                        .inspect_err(|e| warn!("Cannot read {}: {e}", file.display()))
+                        .ok()?;
+                Some(TableRec {
+                    file: file.file_name()?.to_str()?.to_owned(),
+                    th_id: attendance.th_id,
+                    th_name: attendance.th_name,
+                    ss_id: attendance.ss_id,
+                    ss_name: attendance.ss_name,
+                    date_min: attendance.date_min,
+                    date_max: attendance.date_max,
+                    date_filled: attendance.date_filled,
+                    students: attendance.students.len(),
+                    age,
+                    size,
+                    modified,
+                })
+            })
+            .filter(|rec| filter.accepts(rec))
+            .collect::<Vec<_>>();
+    tables.sort_by_key(|rec| rec.modified);
+    Ok(tables)
+}
+
+#[utoipa::path(
+    tag = "tables",
+    params(("direction" = String, Path, description = "inbox or outbox"), TablesFilter),
+    responses(
+        (status = 200, description = "Tables with their metadata", body = Vec<TableRec>),
+        (status = 405, description = "Wrong direction", body = ApiError),
+    )
+)]
+#[get("/attendances/{direction}/list")] // /api
+pub async fn attendances_list(
+    direction: Path<String>,
+    filter: web::Query<TablesFilter>
+) -> ApiResult<impl Responder> {
+    check_direction(direction.as_str())?;
+
+    Ok(web::Json(table_recs(direction.as_str(), &filter)?))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OverwriteQuery {
+    // What to do with an inbox table the teacher has already edited: replace, merge
+    pub mode: Option<String>,
+}
+
+// Text to write into the inbox instead of the existing table, or 409 when the
+// table is edited by the teacher and the mode is not given
+pub fn overwrite(file: &str, body: String, mode: Option<&str>) -> ApiResult<String> {
+    if let Some(mode) = mode.filter(|mode| *mode != "replace" && *mode != "merge") {
+        return Err(ApiError::bad_request("wrong_mode", format!("Wrong mode: {mode}, use replace or merge")))
+    }
+    let id = file.split('.').next().unwrap_or_default();
+    let file_path = format!("attendance/inbox/{file}");
+    let Ok(text) = fs::read_to_string(&file_path) else {
+        return Ok(body)
+    };
+    let existing = match Attendance::parse(id, true, &text) {
+        Ok(existing) => existing,
+        Err(e) => {
+            warn!("Existing {file_path} is invalid, replacing: {e}");
+            return Ok(body)
+        }
+    };
+    let uploaded =
+        Attendance::parse(id, true, &body)
+            .map_err(|e| ApiError::bad_request("invalid_table", format!("Invalid table: {e}")))?;
+
+    match mode {
+        Some("replace") => Ok(body),
+        Some(_) => Ok(existing.merge(&uploaded).to_tsv()),
+        None if existing.has_teacher_edits() && existing.to_tsv() != uploaded.to_tsv() =>
+            Err(ApiError::conflict(
+                "edited_by_teacher",
+                format!("Table {file} is already edited by the teacher, use mode=replace or mode=merge")
+            )),
+        None => Ok(body),
+    }
+}
+
+#[utoipa::path(
+    tag = "tables",
+    params(
+        ("file" = String, Path, description = "File name in the inbox"),
+        ("hash" = String, Path, description = "sha256 of the body, hex"),
+        OverwriteQuery,
+    ),
+    request_body(content = String, description = "Table as TSV or JSON (Attendance)", content_type = "text/tab-separated-values"),
+    responses(
+        (status = 200, description = "Written, ETag of the file"),
+        (status = 400, description = "Invalid table or name", body = ApiError),
+        (status = 403, description = "Wrong hash", body = ApiError),
+        (status = 409, description = "Table is edited by the teacher", body = ApiError),
+        (status = 412, description = "If-Match/If-None-Match failed", body = ApiError),
+    )
+)]
 #[put("/attendance/{file}/{hash}")] // /api
 pub async fn put_attendance(
+    req: HttpRequest,
     path: Path<(String, Option<String>)>,
+    query: web::Query<OverwriteQuery>,
     body: String
-) -> actix_web::Result<impl Responder> {
+) -> ApiResult<impl Responder> {
     let (file, hash) = path.into_inner();
-    put_attendance_with_hash(file, hash, body).await
+    let json = req.content_type() == "application/json";
+    let etag = put_attendance_with_hash(&req, file, hash, query.mode.as_deref(), json, body)?;
+    Ok(HttpResponse::Ok().insert_header((header::ETAG, etag)).body("OK"))
 }
 
+#[utoipa::path(
+    tag = "tables",
+    params(("file" = String, Path, description = "File name in the inbox"), OverwriteQuery),
+    request_body(description = "Table as TSV or JSON", content(
+        (String = "text/tab-separated-values"),
+        (Attendance = "application/json"),
+    )),
+    responses(
+        (status = 200, description = "Written, ETag of the file"),
+        (status = 400, description = "Invalid table, name or digest", body = ApiError),
+        (status = 409, description = "Table is edited by the teacher", body = ApiError),
+        (status = 412, description = "If-Match/If-None-Match failed", body = ApiError),
+    )
+)]
 #[put("/attendance/{file}")] // /api
 pub async fn put_attendance_no_hash(
+    req: HttpRequest,
     file: Path<String>,
+    query: web::Query<OverwriteQuery>,
     body: String
-) -> actix_web::Result<impl Responder> {
-    put_attendance_with_hash(file.into_inner(), None, body).await
+) -> ApiResult<impl Responder> {
+    let json = req.content_type() == "application/json";
+    let etag = put_attendance_with_hash(&req, file.into_inner(), None, query.mode.as_deref(), json, body)?;
+    Ok(HttpResponse::Ok().insert_header((header::ETAG, etag)).body("OK"))
 }
 
-async fn put_attendance_with_hash(
+// Checks and writes the table into the inbox, returns its ETag; body is TSV or JSON
+pub fn put_attendance_with_hash(
+    req: &HttpRequest,
     file: String,
     hash: Option<String>,
+    mode: Option<&str>,
+    json: bool,
     body: String
-) -> actix_web::Result<impl Responder> {
+) -> ApiResult<String> {
     println!("hash={:?}", hash);
     check_file_name(file.as_str())?;
+    routes::check_digest(req, body.as_bytes())?;
 
     if let Some(hash_given) = hash {
         let hash_calculated = sha256::digest(&body);
         println!("given: {hash_given} =?= calculated: {hash_calculated}");
         if hash_given != hash_calculated {
-            return Err(error::ErrorForbidden("Wrong hash!"))
+            return Err(ApiError::new(StatusCode::FORBIDDEN, "wrong_hash", "Wrong hash!"))
         }
     }
 
+    let id = file.split('.').next().unwrap_or_default();
+    let body =
+        if json {
+            Attendance::from_json(id, &body)
+                .map_err(|e| ApiError::bad_request("invalid_table", format!("Invalid table: {e}")))?
+                .to_tsv()
+        } else {
+            Attendance::parse(id, true, &body)
+                .map_err(|e| ApiError::bad_request("invalid_table", format!("Invalid table: {e}")))?;
+            body
+        };
+
+    let file_path = format!("attendance/inbox/{file}");
+    let _lock = attendance::lock_tables();
+    let current = fs::read(&file_path).ok().map(routes::etag);
+    routes::check_preconditions(req, current.as_deref())?;
+    let body = overwrite(&file, body, mode)?;
+    let etag = routes::etag(&body);
+    write_inbox(&file, &body)?;
+    Ok(etag)
+}
+
+pub fn write_inbox(file: &str, body: &str) -> std::io::Result<()> {
     let file_path = format!("attendance/inbox/{file}");
     if fs::exists(&file_path)? {
         // return Err(error::ErrorNotFound("File already exists"))
@@ -84,37 +318,308 @@ async fn put_attendance_with_hash(
     }
     println!("file_path={file_path}");
     fs::write(&file_path, body)?;
+    events::record(EventKind::Uploaded, "inbox", file);
+    Ok(())
+}
 
-    Ok(HttpResponse::Ok().body("OK"))
+// Changes of the student list and dates of an open table; marks of other students are kept
+#[derive(Deserialize, ToSchema)]
+pub struct TableChanges {
+    // student id (positive) -> name
+    #[serde(default)]
+    add: BTreeMap<i32, String>,
+    #[serde(default)]
+    remove: Vec<i32>,
+    // student id -> new name
+    #[serde(default)]
+    rename: BTreeMap<i32, String>,
+    // new date range, marks are moved by date
+    date_min: Option<NaiveDate>,
+    date_max: Option<NaiveDate>,
 }
 
-#[get("/attendance/outbox/{file}")] // /api
-pub async fn get_attendance(file: Path<String>) -> actix_web::Result<impl Responder> {
+impl TableChanges {
+    fn apply(&self, attendance: &mut Attendance) -> Result<(), String> {
+        let check_name = |name: &String| {
+            if name.trim().is_empty() || name.contains(['\t', '\r', '\n']) {
+                Err(format!("Invalid student name: {name:?}"))
+            } else {
+                Ok(())
+            }
+        };
+
+        for st_id in &self.remove {
+            attendance.students
+                .remove(st_id)
+                .ok_or_else(|| format!("No student {st_id} to remove"))?;
+            attendance.comments.remove(st_id);
+            attendance.left.remove(st_id);
+        }
+        for (st_id, name) in &self.rename {
+            check_name(name)?;
+            let (st_name, _) =
+                attendance.students
+                    .get_mut(st_id)
+                    .ok_or_else(|| format!("No student {st_id} to rename"))?;
+            *st_name = name.trim().to_string();
+        }
+        for (st_id, name) in &self.add {
+            check_name(name)?;
+            // ids up to 0 are the blank rows of the teacher page
+            if *st_id <= 0 {
+                return Err(format!("Wrong student id {st_id}"))
+            }
+            if attendance.students.contains_key(st_id) {
+                return Err(format!("Student {st_id} is already in the table"))
+            }
+            attendance.students.insert(*st_id, (name.trim().to_string(), Vec::new()));
+        }
+        if self.date_min.is_some() || self.date_max.is_some() {
+            attendance.set_dates(
+                self.date_min.unwrap_or(attendance.date_min),
+                self.date_max.unwrap_or(attendance.date_max)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    tag = "tables",
+    params(("file" = String, Path, description = "File name in the inbox")),
+    request_body = TableChanges,
+    responses(
+        (status = 200, description = "Changed, ETag of the file"),
+        (status = 400, description = "Invalid changes", body = ApiError),
+        (status = 404, description = "No open table", body = ApiError),
+        (status = 412, description = "If-Match failed", body = ApiError),
+    )
+)]
+#[patch("/attendance/{file}")] // /api
+pub async fn patch_attendance(
+    req: HttpRequest,
+    file: Path<String>,
+    body: String
+) -> ApiResult<HttpResponse> {
     check_file_name(file.as_str())?;
+    routes::check_digest(&req, body.as_bytes())?;
+    let changes: TableChanges =
+        serde_json::from_str(&body)
+            .map_err(|e| ApiError::bad_request("invalid_changes", format!("Invalid changes: {e}")))?;
+
+    let file_path = format!("attendance/inbox/{file}");
+    let _lock = attendance::lock_tables();
+    let contents =
+        fs::read(&file_path)
+            .map_err(|_| ApiError::not_found(format!("No open table {file}")))?;
+    routes::check_preconditions(&req, Some(&routes::etag(&contents)))?;
+
+    let mut attendance = Attendance::read(&file_path)?;
+    changes.apply(&mut attendance).map_err(|e| ApiError::bad_request("invalid_changes", e))?;
+    attendance.write(&file_path);
+    events::record(EventKind::Uploaded, "inbox", &file);
+
+    let etag = routes::etag(attendance.to_tsv());
+    Ok(HttpResponse::Ok().insert_header((header::ETAG, etag)).body("OK"))
+}
+
+// Accept: application/json gives the table as JSON instead of TSV
+fn wants_json(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"))
+}
+
+#[utoipa::path(
+    tag = "tables",
+    params(("file" = String, Path, description = "File name in the outbox")),
+    responses(
+        (status = 200, description = "Sealed table, TSV or JSON by Accept", content(
+            (String = "text/tab-separated-values"),
+            (Attendance = "application/json"),
+        )),
+        (status = 304, description = "Not modified (If-None-Match)"),
+        (status = 404, description = "No sealed table", body = ApiError),
+    )
+)]
+#[get("/attendance/outbox/{file}")] // /api
+pub async fn get_attendance(req: HttpRequest, file: Path<String>) -> ApiResult<impl Responder> {
+    outbox_table(&req, file.as_str(), wants_json(&req))
+}
+
+// Sealed table as TSV or JSON with its ETag, 304 if the client has it
+pub fn outbox_table(req: &HttpRequest, file: &str, json: bool) -> ApiResult<HttpResponse> {
+    check_file_name(file)?;
 
     let file_path = format!("attendance/outbox/{file}");
-    let contents = fs::read_to_string(file_path)?;
-    Ok(HttpResponse::Ok().body(contents))
+    let Ok(contents) = fs::read_to_string(&file_path) else {
+        return Err(ApiError::not_found(format!("No sealed table {file}")))
+    };
+    // JSON is another representation of the same file
+    let hash = sha256::digest(&contents);
+    let etag = if json { format!("\"{hash}-json\"") } else { format!("\"{hash}\"") };
+
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((header::ETAG, etag.as_str()))
+        .insert_header((header::VARY, "Accept"));
+    if routes::not_modified(req, &etag) {
+        return Ok(HttpResponse::NotModified().insert_header((header::ETAG, etag)).finish())
+    }
+    if json {
+        let id = file.split('.').next().unwrap_or_default();
+        let attendance = Attendance::parse(id, false, &contents)?;
+        return Ok(response.json(attendance))
+    }
+    Ok(response.body(contents))
 }
 
+#[utoipa::path(
+    tag = "tables",
+    params(
+        ("direction" = String, Path, description = "inbox or outbox"),
+        ("file" = String, Path, description = "File name"),
+    ),
+    responses(
+        (status = 200, description = "Moved into the trash"),
+        (status = 404, description = "No table", body = ApiError),
+        (status = 412, description = "If-Match failed", body = ApiError),
+    )
+)]
 #[delete("/attendance/{direction}/{file}")] // /api
-pub async fn delete_attendance(params: Path<(String, String)>) -> actix_web::Result<impl Responder> {
+pub async fn delete_attendance(
+    req: HttpRequest,
+    params: Path<(String, String)>
+) -> ApiResult<impl Responder> {
     let (direction, file) = params.into_inner();
-    if direction.as_str() != "inbox" && direction.as_str() != "outbox" {
-        let msg = format!("Wrong direction: {}!", direction.as_str());
-        log::error!("{msg}");
-        return Err(error::ErrorMethodNotAllowed(msg))
-    }
+    check_direction(direction.as_str())?;
 
     check_file_name(file.as_str())?;
 
     let file_path = format!("attendance/{direction}/{file}");
-    if !fs::exists(&file_path)? {
-        // return Err(error::ErrorNotFound("File not exists"))
-        warn!("Warning: file {} not exists", &file_path);
+    let _lock = attendance::lock_tables();
+    let Ok(contents) = fs::read(&file_path) else {
+        return Err(ApiError::not_found(format!("No table {direction}/{file}")))
+    };
+    routes::check_preconditions(&req, Some(&routes::etag(&contents)))?;
+
+    // soft delete: the table can be restored until the timer purges the trash;
+    // earlier deleted tables of the same name are kept as other copies
+    let trash_dir = format!("attendance/trash/{direction}");
+    fs::create_dir_all(&trash_dir)?;
+    let trash_path =
+        (1..)
+            .map(|n| format!("{trash_dir}/{}", trash_name(&file, n)))
+            .find(|path| !std::path::Path::new(path).exists())
+            .unwrap_or_default();
+    fs::rename(&file_path, &trash_path)?;
+    // the age in the trash is counted from the deletion
+    fs::File::options().write(true).open(&trash_path)?.set_modified(std::time::SystemTime::now())?;
+    events::record(EventKind::Deleted, &direction, &file);
+
+    Ok(HttpResponse::Ok().body("OK"))
+}
+
+#[utoipa::path(
+    tag = "tables",
+    params(
+        ("direction" = String, Path, description = "inbox or outbox"),
+        ("file" = String, Path, description = "File name"),
+    ),
+    responses(
+        (status = 200, description = "Restored from the trash"),
+        (status = 404, description = "No deleted table", body = ApiError),
+        (status = 409, description = "Table with the name exists", body = ApiError),
+    )
+)]
+#[post("/attendance/{direction}/{file}/restore")] // /api
+pub async fn restore_attendance(params: Path<(String, String)>) -> ApiResult<impl Responder> {
+    let (direction, file) = params.into_inner();
+    check_direction(direction.as_str())?;
+
+    check_file_name(file.as_str())?;
+
+    let file_path = format!("attendance/{direction}/{file}");
+    let _lock = attendance::lock_tables();
+    // the latest deleted copy
+    let trash_path =
+        fs::read_dir(format!("attendance/trash/{direction}"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| is_trash_copy(&file, &entry.file_name().to_string_lossy()))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .max()
+            .map(|(_, path)| path);
+    let Some(trash_path) = trash_path else {
+        return Err(ApiError::not_found(format!("No deleted table {direction}/{file}")))
+    };
+    if fs::exists(&file_path)? {
+        return Err(ApiError::conflict("already_exists", format!("Table {direction}/{file} already exists")))
     }
 
-    let _ = fs::remove_file(&file_path)?;
+    fs::rename(&trash_path, &file_path)?;
+    events::record(EventKind::Restored, &direction, &file);
 
     Ok(HttpResponse::Ok().body("OK"))
-}
\ No newline at end of file
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReopenRequest {
+    reason: Option<String>,
+}
+
+// Sealed table back to the teacher for corrections, body {"reason": "..."} is optional
+#[utoipa::path(
+    tag = "tables",
+    params(("file" = String, Path, description = "File name in the outbox")),
+    request_body(content = Option<ReopenRequest>, description = "Optional reason"),
+    responses(
+        (status = 200, description = "Moved back to the inbox"),
+        (status = 404, description = "No sealed table", body = ApiError),
+        (status = 409, description = "Table is already open", body = ApiError),
+    )
+)]
+#[post("/attendance/outbox/{file}/reopen")] // /api
+pub async fn reopen_attendance(file: Path<String>, body: String) -> ApiResult<impl Responder> {
+    check_file_name(file.as_str())?;
+    let reason =
+        if body.trim().is_empty() {
+            None
+        } else {
+            serde_json::from_str::<ReopenRequest>(&body)
+                .map_err(|e| ApiError::bad_request("invalid_request", format!("Invalid request: {e}")))?
+                .reason
+        };
+
+    let file_path = format!("attendance/outbox/{file}");
+    let inbox_path = format!("attendance/inbox/{file}");
+    let _lock = attendance::lock_tables();
+    let Ok(contents) = fs::read_to_string(&file_path) else {
+        return Err(ApiError::not_found(format!("No sealed table {file}")))
+    };
+    if fs::exists(&inbox_path)? {
+        return Err(ApiError::conflict("already_exists", format!("Table {file} is already open")))
+    }
+
+    // the sealed version is kept as attendance/revisions/{stem}.{n}.tsv
+    let id = file.split('.').next().unwrap_or_default();
+    let mut attendance = Attendance::parse(id, true, &contents)?;
+    fs::create_dir_all("attendance/revisions")?;
+    let revision_path =
+        (1..)
+            .map(|n| format!("attendance/revisions/{id}.{n}.tsv"))
+            .find(|path| !std::path::Path::new(path).exists())
+            .unwrap_or_default();
+    fs::write(&revision_path, &contents)?;
+    println!("Revision of {file}: {revision_path}");
+
+    attendance.date_filled = None;
+    attendance.correction = Some(reason.as_deref().map(clean_comment).unwrap_or_default());
+    fs::write(&inbox_path, attendance.to_tsv())?;
+    fs::remove_file(&file_path)?;
+    events::record(EventKind::Reopened, "inbox", &file);
+
+    Ok(HttpResponse::Ok().body("OK"))
+}
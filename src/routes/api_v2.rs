@@ -0,0 +1,87 @@
+use actix_web::{get, put, HttpRequest, HttpResponse, Responder, web};
+use actix_web::http::header;
+use actix_web::web::Path;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::attendance::Attendance;
+use crate::routes::api_error::{ApiError, ApiResult};
+use crate::routes::api_events::{self, EventsPage, EventsQuery};
+use crate::routes::api_tables::{self, OverwriteQuery, TableRec, TablesFilter};
+
+// Version 2 of the API: JSON only, tables under /tables/{direction}
+
+#[derive(Serialize, ToSchema)]
+pub struct PutResult {
+    file: String,
+    etag: String,
+}
+
+#[utoipa::path(
+    tag = "v2",
+    params(("direction" = String, Path, description = "inbox or outbox"), TablesFilter),
+    responses(
+        (status = 200, description = "Tables with their metadata", body = Vec<TableRec>),
+        (status = 405, description = "Wrong direction", body = ApiError),
+    )
+)]
+#[get("/tables/{direction}")] // /api/v2
+pub async fn tables(
+    direction: Path<String>,
+    filter: web::Query<TablesFilter>
+) -> ApiResult<impl Responder> {
+    api_tables::check_direction(direction.as_str())?;
+    Ok(web::Json(api_tables::table_recs(direction.as_str(), &filter)?))
+}
+
+#[utoipa::path(
+    tag = "v2",
+    params(("file" = String, Path, description = "File name in the outbox")),
+    responses(
+        (status = 200, description = "Sealed table", body = Attendance),
+        (status = 304, description = "Not modified (If-None-Match)"),
+        (status = 404, description = "No sealed table", body = ApiError),
+    )
+)]
+#[get("/tables/outbox/{file}")] // /api/v2
+pub async fn outbox_table(req: HttpRequest, file: Path<String>) -> ApiResult<HttpResponse> {
+    api_tables::outbox_table(&req, file.as_str(), true)
+}
+
+#[utoipa::path(
+    tag = "v2",
+    params(("file" = String, Path, description = "File name in the inbox"), OverwriteQuery),
+    request_body = Attendance,
+    responses(
+        (status = 200, description = "Written", body = PutResult),
+        (status = 400, description = "Invalid table, name or digest", body = ApiError),
+        (status = 409, description = "Table is edited by the teacher", body = ApiError),
+        (status = 412, description = "If-Match/If-None-Match failed", body = ApiError),
+    )
+)]
+#[put("/tables/inbox/{file}")] // /api/v2
+pub async fn put_inbox_table(
+    req: HttpRequest,
+    file: Path<String>,
+    query: web::Query<OverwriteQuery>,
+    body: String
+) -> ApiResult<HttpResponse> {
+    let file = file.into_inner();
+    let etag = api_tables::put_attendance_with_hash(&req, file.clone(), None, query.mode.as_deref(), true, body)?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag.as_str()))
+        .json(PutResult { file, etag }))
+}
+
+#[utoipa::path(
+    tag = "v2",
+    params(EventsQuery),
+    responses(
+        (status = 200, description = "Events after the cursor", body = EventsPage),
+        (status = 400, description = "Invalid query", body = ApiError),
+    )
+)]
+#[get("/events")] // /api/v2
+pub async fn events(query: web::Query<EventsQuery>) -> impl Responder {
+    web::Json(api_events::events_page(&query).await)
+}
@@ -1,4 +1,3 @@
-use std::arch::x86_64;
 use std::fs::DirEntry;
 use std::{fs, io};
 use std::ffi::OsStr;
@@ -8,7 +7,7 @@ use actix_web::{get, post, web, HttpResponse, Responder, HttpRequest, HttpMessag
 use actix_identity::Identity;
 //use actix_session::storage::RedisSessionStore;
 
-use ::captcha::{Captcha, CaptchaName, Difficulty, Geometry};
+use ::captcha::{Captcha, Geometry};
 use ::captcha::filters::{Cow, Noise, Wave};
 use rand::{RngCore, rngs::OsRng};
 use time::{OffsetDateTime, Duration};
@@ -18,7 +17,6 @@ use sha2::Sha256;
 use hmac::{Hmac, Mac};
 type HmacSha256 = Hmac<Sha256>;
 
-use actix_web::http::header::ContentType;
 use tera::{Context, Tera};
 use crate::attendance::Attendance;
 use crate::routes::login::Login;
@@ -34,9 +32,9 @@ fn read_entity<'a>(th_id: &'a str) -> impl Fn(DirEntry) -> Option<Attendance> +
         } else {
             path.file_stem()
                 .and_then(|name| name.to_str())
-                .filter(|name| String::from("0000") == th_id || name.starts_with(th_id))
+                .filter(|name| "0000" == th_id || name.starts_with(th_id))
                 .map(|_| Attendance::read(path.to_str().unwrap()))
-                .and_then(|r| r.ok())
+                .and_then(|r| r.map_err(|e| println!("Cannot read {}: {e}", path.display())).ok())
         }
     }
 }
@@ -67,7 +65,7 @@ async fn index(
         let is_admin: bool = id.parse() == Ok(0);
         let opens =
             read_attendance_dir(id.as_str())("attendance/inbox")
-                .unwrap_or(Vec::new()); // todo: report errors
+                .unwrap_or_default(); // todo: report errors
 
         let mut context = Context::new();
         context.insert("is_admin", &is_admin);
@@ -209,7 +207,7 @@ pub fn create_signed_token(answer: &str, ttl_seconds: i64) -> String {
     payload.extend_from_slice(&answer_hash);
 
     // подпись HMAC
-    let mut mac = HmacSha256::new_from_slice(&*captcha_secret).expect("HMAC can take key of any size");
+    let mut mac = HmacSha256::new_from_slice(&captcha_secret).expect("HMAC can take key of any size");
     mac.update(&payload);
     let signature = mac.finalize().into_bytes();
 
@@ -250,7 +248,7 @@ fn captcha_internal(tera: &Tera) -> String {
     let mut context = Context::new();
     context.insert("b64", b64.as_str());
     context.insert("token", token.as_str());
-    context.insert("valid_sec", (&(*valid_sec)).into());
+    context.insert("valid_sec", &*valid_sec);
 
     tera
         .render("captcha.html", &context)
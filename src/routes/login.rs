@@ -36,7 +36,7 @@ impl Login {
         let (payload, signature) = token_bytes.split_at(payload_len);
 
         // проверка подписи
-        let mut mac = HmacSha256::new_from_slice(&*captcha_secret).map_err(|_| "hmac error")?;
+        let mut mac = HmacSha256::new_from_slice(&captcha_secret).map_err(|_| "hmac error")?;
         mac.update(payload);
         mac.verify_slice(signature).map_err(|_| "invalid signature".to_string())?;
 
@@ -63,7 +63,7 @@ impl Login {
     // None means ok
     pub fn check_captcha(&self) -> Option<String> {
         if let Some(token) = &self.token && let Some(captcha) = &self.captcha {
-            Self::verify_signed_token(&token, &captcha).err()
+            Self::verify_signed_token(token, captcha).err()
         } else {
             Some("Captcha required!".to_string())
         }
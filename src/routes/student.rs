@@ -1,22 +1,37 @@
 use actix_identity::Identity;
 use actix_web::{get, put, web, HttpRequest, HttpResponse, Responder};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use actix_web::http::StatusCode;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use actix_web::http::header;
+use csv::StringRecord;
 use serde::{Deserialize, Serialize};
-use crate::routes;
+use utoipa::ToSchema;
+use crate::{routes, student_index, students_limit};
+use crate::routes::api_error::{ApiError, ApiResult};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
 pub struct Student {
-    id: i16,
-    #[serde(rename(deserialize = "ФИО"))]
-    name: String,
+    pub id: i32,
+    // "ФИО" in students.tsv, "name" in JSON
+    #[serde(rename(deserialize = "ФИО"), alias = "name")]
+    pub name: String,
 }
 
 pub const STUDENTS_FILE: &str = "students.tsv";
 pub const TEACHERS_FILE: &str = "teachers.tsv";
 
-pub fn read_students() -> csv::Result<HashMap<i16, String>> {
+// Serializes changes of students.tsv
+lazy_static::lazy_static! {
+    static ref students_lock: Mutex<()> = Mutex::new(());
+}
+
+pub fn lock_students() -> MutexGuard<'static, ()> {
+    students_lock.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn read_students() -> csv::Result<HashMap<i32, String>> {
     csv::ReaderBuilder::new()
         .delimiter(b'\t')
         .from_path(STUDENTS_FILE)?
@@ -25,16 +40,154 @@ pub fn read_students() -> csv::Result<HashMap<i16, String>> {
         .collect()
 }
 
+#[utoipa::path(
+    tag = "directories",
+    responses(
+        (status = 200, description = "sha256 of the students file, hex", body = String),
+        (status = 404, description = "No file", body = ApiError),
+    )
+)]
 #[get("/students/hash")] // /api
-pub async fn students_hash() -> actix_web::Result<impl Responder> {
+pub async fn students_hash() -> ApiResult<impl Responder> {
     let hash = sha256::try_digest(std::path::Path::new(STUDENTS_FILE))?;
-    Ok(HttpResponse::Ok().body(hash))
+    Ok(HttpResponse::Ok().insert_header((header::ETAG, format!("\"{hash}\""))).body(hash))
+}
+
+/// Проверяет содержимое students.tsv: столбцы id и ФИО, id — целые (i32) без повторов,
+/// ФИО не пустые и в одну строку. Ошибки — с номерами строк файла.
+pub fn parse_students(contents: &str) -> Result<Vec<Student>, Vec<String>> {
+    let mut reader = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(contents.as_bytes());
+    let headers = reader.headers().map_err(|e| vec![e.to_string()])?.clone();
+    let missing: Vec<String> =
+        ["id", "ФИО"]
+            .iter()
+            .filter(|column| !headers.iter().any(|header| header == **column))
+            .map(|column| format!("No column {column}"))
+            .collect();
+    if !missing.is_empty() {
+        return Err(missing)
+    }
+
+    let mut ids = HashSet::new();
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    for record in reader.records() {
+        let student =
+            record.and_then(|record| {
+                let line = record.position().map(|pos| pos.line()).unwrap_or_default();
+                record.deserialize::<Student>(Some(&headers)).map(|student| (line, student))
+            });
+        match student {
+            Err(e) => errors.push(e.to_string()),
+            Ok((line, student)) if student.name.trim().is_empty() || student.name.contains(['\t', '\n', '\r']) =>
+                errors.push(format!("Line {line}: wrong name of student {}", student.id)),
+            Ok((line, student)) if !ids.insert(student.id) =>
+                errors.push(format!("Line {line}: duplicate id {}", student.id)),
+            Ok((_, student)) => valid.push(student),
+        }
+    }
+    if errors.is_empty() { Ok(valid) } else { Err(errors) }
+}
+
+fn invalid_students(errors: Vec<String>) -> ApiError {
+    ApiError::bad_request("invalid_students", format!("Invalid {STUDENTS_FILE}: {}", errors[0])).details(errors)
+}
+
+// Writes through a temporary file, so the old file stays whole until the new one is complete
+fn write_atomic(file: &str, contents: &[u8]) -> io::Result<()> {
+    let tmp_file = format!("{file}.tmp");
+    fs::write(&tmp_file, contents)?;
+    fs::rename(&tmp_file, file)
+}
+
+/// Строки students.tsv; прочие столбцы файла сохраняются при изменениях через JSON
+pub struct StudentRecords {
+    headers: StringRecord,
+    rows: Vec<StringRecord>,
+}
+
+impl StudentRecords {
+    /// Читает students.tsv; вызывать под lock_students()
+    pub fn read() -> io::Result<StudentRecords> {
+        let mut reader = csv::ReaderBuilder::new().delimiter(b'\t').from_path(STUDENTS_FILE)?;
+        Ok(StudentRecords {
+            headers: reader.headers()?.clone(),
+            rows: reader.records().collect::<csv::Result<_>>()?,
+        })
+    }
+
+    fn column(&self, name: &str) -> Option<usize> {
+        self.headers.iter().position(|header| header == name)
+    }
+
+    fn position(&self, id: i32) -> Option<usize> {
+        let id_column = self.column("id")?;
+        self.rows.iter().position(|row| row.get(id_column).and_then(|s| s.trim().parse().ok()) == Some(id))
+    }
+
+    pub fn contains(&self, id: i32) -> bool {
+        self.position(id).is_some()
+    }
+
+    /// Меняет ФИО ученика или добавляет его в конец файла
+    pub fn set(&mut self, id: i32, name: &str) {
+        let (Some(id_column), Some(name_column)) = (self.column("id"), self.column("ФИО")) else {
+            return
+        };
+        let pos = self.position(id);
+        let mut row: Vec<String> = match pos {
+            Some(pos) => self.rows[pos].iter().map(str::to_string).collect(),
+            None => vec![String::new(); self.headers.len()],
+        };
+        row[id_column] = id.to_string();
+        row[name_column] = name.to_string();
+        match pos {
+            Some(pos) => self.rows[pos] = StringRecord::from(row),
+            None => self.rows.push(StringRecord::from(row)),
+        }
+    }
+
+    pub fn remove(&mut self, id: i32) -> bool {
+        self.position(id).map(|pos| self.rows.remove(pos)).is_some()
+    }
+
+    /// Проверяет и записывает файл целиком; при ошибке файл не меняется
+    pub fn write(&self) -> ApiResult<Vec<Student>> {
+        let mut writer = csv::WriterBuilder::new().delimiter(b'\t').from_writer(Vec::new());
+        writer.write_record(&self.headers).map_err(io::Error::other)?;
+        for row in &self.rows {
+            writer.write_record(row).map_err(io::Error::other)?;
+        }
+        let contents = writer.into_inner().map_err(io::Error::other)?;
+        let valid = parse_students(&String::from_utf8_lossy(&contents)).map_err(invalid_students)?;
+        write_atomic(STUDENTS_FILE, &contents)?;
+        student_index::reload();
+        Ok(valid)
+    }
+}
+
+// PUT of a whole directory file honoring If-Match/If-None-Match
+fn put_file(req: &HttpRequest, file: &str, body: String) -> ApiResult<HttpResponse> {
+    routes::check_digest(req, body.as_bytes())?;
+    let current = fs::read(file).ok().map(routes::etag);
+    routes::check_preconditions(req, current.as_deref())?;
+
+    let etag = routes::etag(&body);
+    write_atomic(file, body.as_bytes())?;
+    Ok(HttpResponse::Ok().insert_header((header::ETAG, etag)).body("OK"))
+}
+
+#[derive(Deserialize)]
+pub struct StudentsQuery {
+    filter: Option<String>,
+    // no more than students_limit
+    limit: Option<usize>,
 }
 
 #[get("/students")]
 pub async fn students(
     req: HttpRequest,
-    query: web::Query<HashMap<String, String>>,
+    query: web::Query<StudentsQuery>,
     user: Option<Identity>,
 ) -> impl Responder {
     routes::user_agent_info(&req, "students");
@@ -42,49 +195,60 @@ pub async fn students(
         println!("no auth!");
         return HttpResponse::Forbidden().finish();
     }
-    let filter =
-        query.get("filter")
-            .filter(|s| s.chars().count() >= 2)
-            .map(|s| s.to_uppercase());
-    if filter.is_none() {
+    let Some(filter) = query.filter.as_ref().filter(|s| s.trim().chars().count() >= 2) else {
         println!("No filter!");
         return HttpResponse::BadRequest().finish();
-    }
-    let filter = filter.unwrap();
-
-    match read_students() {
-        Ok(students) => {
-            let output: Vec<Student> =
-                students
-                    .iter()
-                    .filter(|(_, name)| name.to_uppercase().starts_with(filter.to_uppercase().as_str()))
-                    .map(|(&id, name)| Student { id, name: name.clone() })
-                    .collect();
-
-            HttpResponse::Ok().json(output)
-
-        },
-        Err(e) => {
-            HttpResponse::build(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(format!("Get student's list failed: {:?}", e))
-        }
-    }
+    };
+    let limit = query.limit.unwrap_or(*students_limit).min(*students_limit);
+
+    HttpResponse::Ok().json(student_index::search(filter, limit))
 }
 
+#[utoipa::path(
+    tag = "directories",
+    request_body(content = String, description = "students.tsv", content_type = "text/tab-separated-values"),
+    responses(
+        (status = 200, description = "Written, ETag of the file"),
+        (status = 400, description = "Invalid students file or digest mismatch", body = ApiError),
+        (status = 412, description = "If-Match/If-None-Match failed", body = ApiError),
+    )
+)]
 #[put("/students")]
-pub async fn put_students(body: String) -> impl Responder {
-    fs::write(STUDENTS_FILE, body).map(|_| "OK")
+pub async fn put_students(req: HttpRequest, body: String) -> ApiResult<HttpResponse> {
+    // the file is replaced only when every row is valid
+    let valid = parse_students(&body).map_err(invalid_students)?;
+    let _lock = lock_students();
+    let response = put_file(&req, STUDENTS_FILE, body)?;
+    student_index::reload();
+    println!("{STUDENTS_FILE}: {} students", valid.len());
+    Ok(response)
 }
 
 // copy-paste /teachers/hash
+#[utoipa::path(
+    tag = "directories",
+    responses(
+        (status = 200, description = "sha256 of the teachers file, hex", body = String),
+        (status = 404, description = "No file", body = ApiError),
+    )
+)]
 #[get("/teachers/hash")] // /api
-pub async fn teachers_hash() -> actix_web::Result<impl Responder> {
+pub async fn teachers_hash() -> ApiResult<impl Responder> {
     let hash = sha256::try_digest(std::path::Path::new(TEACHERS_FILE))?;
-    Ok(HttpResponse::Ok().body(hash))
+    Ok(HttpResponse::Ok().insert_header((header::ETAG, format!("\"{hash}\""))).body(hash))
 }
 
 // copy-paste put /teachers
+#[utoipa::path(
+    tag = "directories",
+    request_body(content = String, description = "teachers.tsv", content_type = "text/tab-separated-values"),
+    responses(
+        (status = 200, description = "Written, ETag of the file"),
+        (status = 400, description = "Digest mismatch", body = ApiError),
+        (status = 412, description = "If-Match/If-None-Match failed", body = ApiError),
+    )
+)]
 #[put("/teachers")]
-pub async fn put_teachers(body: String) -> impl Responder {
-    fs::write(TEACHERS_FILE, body).map(|_| "OK")
+pub async fn put_teachers(req: HttpRequest, body: String) -> ApiResult<HttpResponse> {
+    put_file(&req, TEACHERS_FILE, body)
 }
\ No newline at end of file
@@ -1,6 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use crate::{attendance::Attendance, teachrec::TeachRec};
+use chrono::NaiveDate;
+use crate::{attendance::{self, Attendance, clean_comment}, mark::Mark, teachrec::TeachRec};
+use crate::events::{self, EventKind};
+use crate::webhook;
 use actix_identity::Identity;
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
 use actix_web::web::Redirect;
@@ -104,10 +107,17 @@ async fn table(
 
         let file_name = format!("attendance/inbox/{}.tsv", name);
         let file_name = file_name.as_str();
-        let mut attendance = Attendance::read(file_name).unwrap(); // todo
+        let _lock = attendance::lock_tables();
+        let mut attendance = match Attendance::read(file_name) {
+            Ok(attendance) => attendance,
+            Err(e) => {
+                println!("Cannot read {file_name}: {e}");
+                return HttpResponse::InternalServerError().body(format!("Cannot read the table: {e}"))
+            }
+        };
         let dr = attendance.date_range();
 
-        let students =
+        let rows: Vec<(i32, i32, String)> =
             attendance
                 .students
                 .iter()
@@ -122,7 +132,14 @@ async fn table(
                     }
                     (id, Some(st_name))
                 })
-                .chain(Attendance::blank_range().map(|id| (id, None)))
+                .chain(
+                    // rows for new students, including ones added in the browser
+                    parsed_form
+                        .keys()
+                        .filter_map(|key| key.strip_prefix("IN")?.parse::<i32>().ok())
+                        .filter(|&id| id < 0)
+                        .map(|id| (id, None))
+                )
                 .filter_map(|(id, st_name)| {
                     let st_id: i32 =
                         parsed_form.get(format!("IN{id:05}").as_str())
@@ -134,33 +151,100 @@ async fn table(
                             .to_owned();
                     Some((id, st_id, st_name))
                 })
+                .collect();
+
+        // last date in the group of students who left it (by form id)
+        let left: HashMap<i32, NaiveDate> =
+            rows
+                .iter()
+                .filter_map(|(id, _, _)| {
+                    let d = parsed_form.get(&format!("L{id:05}"))?;
+                    Some((*id, NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()?))
+                })
+                .collect();
+        let in_group = |id: &i32, d: &NaiveDate| left.get(id).is_none_or(|left| d <= left);
+
+        let students =
+            rows
+                .iter()
                 .map(|(id, st_id, st_name)| {
-                    let marks: Vec<String> =
+                    let marks: Result<Vec<Option<Mark>>, String> =
                         dr
                             .iter()
                             .map(|d| {
+                                if !in_group(id, d) {
+                                    return Ok(None)
+                                }
                                 let field: String = format!("S{id:05}D{d}");
                                 parsed_form
                                     .get(&field)
-                                    .map(|v| v.parse::<i16>().map_or(String::new(), |_| v.clone()))
-                                    .unwrap_or(String::new())
+                                    .map_or(Ok(None), |v| Mark::parse_input(v))
+                                    .map_err(|e| format!("{e}: {st_name}, {}", d.format("%d.%m.%Y")))
+                            })
+                            .collect();
+                    marks.map(|marks| (*st_id, (st_name.clone(), marks)))
+                })
+                .collect::<Result<_, String>>();
 
+        attendance.comments =
+            rows
+                .iter()
+                .map(|(id, st_id, _)| {
+                    let comments: BTreeMap<NaiveDate, String> =
+                        dr
+                            .iter()
+                            .filter(|d| in_group(id, d))
+                            .filter_map(|d| {
+                                let comment = parsed_form.get(&format!("C{id:05}D{d}"))?;
+                                Some((*d, clean_comment(comment))).filter(|(_, c)| !c.is_empty())
                             })
                             .collect();
-                    (st_id, (st_name, marks))
+                    (*st_id, comments)
                 })
+                .filter(|(_, comments)| !comments.is_empty())
+                .collect();
+
+        attendance.left =
+            rows
+                .iter()
+                .filter_map(|(id, st_id, _)| Some((*st_id, *left.get(id)?)))
                 .collect();
 
-        attendance.students = students;
+        attendance.students = match students {
+            Ok(students) => students,
+            Err(e) => return HttpResponse::BadRequest()
+                .content_type("text/plain; charset=utf-8")
+                .body(e),
+        };
+
+        attendance.note =
+            parsed_form
+                .get("note")
+                .map(|s| clean_comment(s))
+                .filter(|s| !s.is_empty());
+        attendance.topics =
+            dr
+                .iter()
+                .filter_map(|d| {
+                    let topic = clean_comment(parsed_form.get(&format!("T{d}"))?);
+                    Some((*d, topic)).filter(|(_, t)| !t.is_empty())
+                })
+                .collect();
 
         let file_name = format!("{}.tsv", name); // todo
         let file_name_open = format!("attendance/inbox/{file_name}");
         let file_name_closed = format!("attendance/outbox/{file_name}");
+        if seal {
+            // the requested corrections are done
+            attendance.correction = None;
+        }
         attendance.write(file_name_open.as_str());
+        events::record(EventKind::Saved, "inbox", &file_name);
 
         let origin = request.clone().uri().path().to_string();
         let redirect = if seal {
             fs::rename(file_name_open.as_str(), file_name_closed.as_str()).expect("Cannot move!!"); // todo
+            webhook::enqueue(&events::record(EventKind::Sealed, "outbox", &file_name));
             String::from("/")
         } else { origin };
         Redirect::to(redirect).see_other().respond_to(&request).map_into_boxed_body()
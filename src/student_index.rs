@@ -0,0 +1,109 @@
+use std::fs;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use crate::routes::student::{self, Student, STUDENTS_FILE};
+
+// Students directory in memory for the /students autocomplete:
+// loaded at startup, reloaded after PUT /api/students and when students.tsv changes on disk
+
+#[derive(Default)]
+struct StudentIndex {
+    // modification time of the loaded students.tsv
+    modified: Option<SystemTime>,
+    // sorted by the normalized name
+    students: Vec<Student>,
+    // normalized names, parallel to students
+    names: Vec<String>,
+    // (word of a normalized name, index in students), sorted by the word
+    words: Vec<(String, usize)>,
+}
+
+lazy_static::lazy_static! {
+    static ref index: RwLock<Arc<StudentIndex>> = RwLock::new(Arc::new(StudentIndex::default()));
+}
+
+/// Регистр и ё не различаются при поиске
+fn normalize(s: &str) -> String {
+    s.to_lowercase().replace('ё', "е")
+}
+
+fn modified() -> Option<SystemTime> {
+    fs::metadata(STUDENTS_FILE).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl StudentIndex {
+    fn new(mut students: Vec<Student>, modified: Option<SystemTime>) -> StudentIndex {
+        students.sort_by_cached_key(|student| (normalize(&student.name), student.id));
+        let names: Vec<String> = students.iter().map(|student| normalize(&student.name)).collect();
+        let mut words: Vec<(String, usize)> =
+            names
+                .iter()
+                .enumerate()
+                .flat_map(|(i, name)| name.split_whitespace().map(move |word| (word.to_string(), i)))
+                .collect();
+        words.sort_unstable();
+        StudentIndex { modified, students, names, words }
+    }
+}
+
+/// Перечитывает students.tsv; если файл неверен, остаётся прежний список
+pub fn reload() {
+    let modified = modified();
+    let loaded =
+        fs::read_to_string(STUDENTS_FILE)
+            .map_err(|e| vec![e.to_string()])
+            .and_then(|contents| student::parse_students(&contents));
+    let mut index_guard = index.write().unwrap_or_else(|e| e.into_inner());
+    match loaded {
+        Ok(students) => {
+            println!("{STUDENTS_FILE}: {} students loaded", students.len());
+            *index_guard = Arc::new(StudentIndex::new(students, modified));
+        }
+        Err(errors) => {
+            println!("Cannot load {STUDENTS_FILE}: {}", errors.join("; "));
+            // do not try again until the file changes
+            let students = index_guard.students.clone();
+            *index_guard = Arc::new(StudentIndex::new(students, modified));
+        }
+    }
+}
+
+fn current() -> Arc<StudentIndex> {
+    let current = index.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if current.modified == modified() {
+        return current
+    }
+    reload();
+    index.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Ученики, у которых каждое слово запроса — начало какого-то слова ФИО,
+/// по алфавиту, не больше limit
+pub fn search(filter: &str, limit: usize) -> Vec<Student> {
+    let current = current();
+    let query: Vec<String> = normalize(filter).split_whitespace().map(str::to_string).collect();
+    let Some((first, rest)) = query.split_first() else {
+        return Vec::new()
+    };
+
+    let start = current.words.partition_point(|(word, _)| word < first);
+    let mut found: Vec<usize> =
+        current.words[start..]
+            .iter()
+            .take_while(|(word, _)| word.starts_with(first.as_str()))
+            .map(|&(_, i)| i)
+            .collect();
+    // indices in students are in the name order
+    found.sort_unstable();
+    found.dedup();
+
+    found
+        .into_iter()
+        .filter(|&i| {
+            rest.iter().all(|part| current.names[i].split_whitespace().any(|word| word.starts_with(part.as_str())))
+        })
+        .take(limit)
+        .map(|i| current.students[i].clone())
+        .collect()
+}
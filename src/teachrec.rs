@@ -17,7 +17,7 @@ impl TeachRec {
         let th_id: i32 = login.login.parse().ok()?;
 
         let file = File::open(TEACHERS_FILE)
-            .expect(format!("No {TEACHERS_FILE} file!!").as_str());
+            .unwrap_or_else(|_| panic!("No {TEACHERS_FILE} file!!"));
 
         csv::ReaderBuilder::new()
             .delimiter(b'\t') // Specify tab as the delimiter
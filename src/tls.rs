@@ -0,0 +1,117 @@
+use std::fs;
+use std::io::{BufReader, Error, ErrorKind, Result};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web::http::header;
+use rustls::ServerConfig;
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+
+use crate::{port, tls_cert, tls_key};
+
+// HTTPS: certificate and key from tls.cert and tls.key, reloaded by the timer when the files change
+
+/// Сертификат сервера, который можно заменить на лету
+#[derive(Debug)]
+struct CertReloader {
+    key: RwLock<Arc<CertifiedKey>>,
+    // modification times of the cert and key files of the loaded certificate
+    modified: Mutex<(SystemTime, SystemTime)>,
+}
+
+impl ResolvesServerCert for CertReloader {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.key.read().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref cert_reloader: Option<Arc<CertReloader>> =
+        tls_cert.as_ref().zip(tls_key.as_ref()).map(|(cert, key)| {
+            let modified = (modified(cert).expect("tls.cert"), modified(key).expect("tls.key"));
+            let key = load(cert, key).expect("cannot load TLS certificate");
+            Arc::new(CertReloader { key: RwLock::new(Arc::new(key)), modified: Mutex::new(modified) })
+        });
+}
+
+fn modified(file: &str) -> Result<SystemTime> {
+    fs::metadata(file)?.modified()
+}
+
+fn load(cert_file: &str, key_file: &str) -> Result<CertifiedKey> {
+    let certs =
+        rustls_pemfile::certs(&mut BufReader::new(fs::File::open(cert_file)?))
+            .collect::<Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, format!("No certificates in {cert_file}")))
+    }
+    let key =
+        rustls_pemfile::private_key(&mut BufReader::new(fs::File::open(key_file)?))?
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("No private key in {key_file}")))?;
+    let key = rustls::crypto::ring::sign::any_supported_type(&key).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let certified_key = CertifiedKey::new(certs, key);
+    certified_key.keys_match().map_err(|e| Error::new(ErrorKind::InvalidData, format!("{cert_file} and {key_file}: {e}")))?;
+    Ok(certified_key)
+}
+
+/// Включён ли HTTPS (заданы tls.cert и tls.key)
+pub fn enabled() -> bool {
+    cert_reloader.is_some()
+}
+
+/// Настройки rustls для bind_rustls_0_23; None, если HTTPS не включён
+pub fn server_config() -> Option<ServerConfig> {
+    cert_reloader.as_ref().map(|reloader| {
+        let mut config =
+            ServerConfig::builder()
+                .with_no_client_auth()
+                .with_cert_resolver(reloader.clone());
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        config
+    })
+}
+
+/// Перечитывает сертификат и ключ, если файлы изменились.
+/// Новые соединения получают новый сертификат, открытые остаются со старым.
+pub fn reload_if_changed() {
+    let (Some(reloader), Some(cert), Some(key)) = (cert_reloader.as_ref(), tls_cert.as_ref(), tls_key.as_ref()) else {
+        return
+    };
+    let Ok(now_modified) = modified(cert).and_then(|cert_time| Ok((cert_time, modified(key)?))) else {
+        return
+    };
+    let mut modified_guard = reloader.modified.lock().unwrap_or_else(|e| e.into_inner());
+    if *modified_guard == now_modified {
+        return
+    }
+    match load(cert, key) {
+        Ok(certified_key) => {
+            *reloader.key.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(certified_key);
+            *modified_guard = now_modified;
+            println!("TLS certificate reloaded from {cert}");
+        }
+        // e.g. the cert is already replaced but the key is not yet: try again on the next tick
+        Err(e) => println!("Cannot reload TLS certificate: {e}"),
+    }
+}
+
+/// Ответ слушателя tls.redirect_port: переадресация на тот же путь по HTTPS
+pub async fn redirect_to_https(req: HttpRequest) -> HttpResponse {
+    let conn = req.connection_info();
+    let req_host = conn.host();
+    // host without port, also for [ipv6]:port
+    let req_host = match req_host.rsplit_once(':') {
+        Some((name, p)) if !p.contains(']') && p.parse::<u16>().is_ok() => name,
+        _ => req_host,
+    };
+    let location = match *port {
+        443 => format!("https://{req_host}{}", req.uri()),
+        p => format!("https://{req_host}:{p}{}", req.uri()),
+    };
+    // 308 keeps the method and body of API requests
+    HttpResponse::PermanentRedirect()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
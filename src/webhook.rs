@@ -0,0 +1,185 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+type HmacSha256 = Hmac<Sha256>;
+
+use crate::events::Event;
+use crate::{webhook_max_attempts, webhook_queue, webhook_secret, webhook_url};
+
+// Notification waiting for delivery, one JSON file in the webhook_queue folder
+#[derive(Debug, Serialize, Deserialize)]
+struct Delivery {
+    event: Event,
+    attempts: u32,
+    next_try: DateTime<Utc>,
+}
+
+/// Ставит в очередь уведомление о закрытой таблице (если задан webhook.url).
+pub fn enqueue(event: &Event) {
+    if webhook_url.is_none() {
+        return
+    }
+
+    let delivery = Delivery { event: event.clone(), attempts: 0, next_try: Utc::now() };
+    let path = format!("{}/{:010}.json", *webhook_queue, event.id);
+    let written =
+        fs::create_dir_all(webhook_queue.as_str())
+            .and_then(|_| fs::write(&path, serde_json::to_string(&delivery)?));
+    if let Err(e) = written {
+        println!("Cannot enqueue webhook {path}: {e}");
+    }
+}
+
+/// HMAC-SHA256 of the body in hex, sent as "X-Signature: sha256=<hex>"
+pub fn signature(secret: &[u8], body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC can take key of any size");
+    mac.update(body.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+pub fn post(url: &str, secret: &[u8], body: &str) -> Result<(), String> {
+    ureq::post(url)
+        .timeout(Duration::from_secs(10))
+        .set("Content-Type", "application/json")
+        .set("X-Signature", format!("sha256={}", signature(secret, body)).as_str())
+        .send_string(body)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// Next attempt in 1, 2, 4 ... minutes, but no later than in a day
+fn backoff(attempts: u32) -> chrono::Duration {
+    chrono::Duration::minutes(1 << attempts.min(10)).min(chrono::Duration::days(1))
+}
+
+fn deliver_one(path: &Path, url: &str) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut delivery: Delivery = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    if delivery.next_try > Utc::now() {
+        return Ok(())
+    }
+
+    let body = serde_json::to_string(&delivery.event).map_err(|e| e.to_string())?;
+    match post(url, &webhook_secret, &body) {
+        Ok(()) => {
+            println!("Webhook delivered: {body}");
+            fs::remove_file(path).map_err(|e| e.to_string())
+        },
+        Err(e) => {
+            delivery.attempts += 1;
+            println!("Webhook delivery {} failed (attempt {}): {e}", path.display(), delivery.attempts);
+            if delivery.attempts >= *webhook_max_attempts {
+                println!("Webhook {} dropped after {} attempts", path.display(), delivery.attempts);
+                return fs::remove_file(path).map_err(|e| e.to_string())
+            }
+            delivery.next_try = Utc::now() + backoff(delivery.attempts);
+            let text = serde_json::to_string(&delivery).map_err(|e| e.to_string())?;
+            fs::write(path, text).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Отправляет накопившиеся уведомления.
+pub fn deliver() {
+    let Some(url) = webhook_url.as_deref() else {
+        return
+    };
+    let Ok(entries) = fs::read_dir(webhook_queue.as_str()) else {
+        return
+    };
+
+    let mut paths: Vec<_> =
+        entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+    paths.sort();
+
+    for path in paths {
+        if let Err(e) = deliver_one(&path, url) {
+            println!("Webhook queue error {}: {e}", path.display());
+        }
+    }
+}
+
+/// Запускает отправку уведомлений в отдельном потоке (если задан webhook.url):
+/// POST ждёт ответа до 10 секунд и не должен задерживать таймер.
+pub fn start() {
+    if webhook_url.is_none() {
+        return
+    }
+    thread::spawn(|| loop {
+        deliver();
+        thread::sleep(Duration::from_secs(5));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // Local HTTP stand-in: answers one request with the status, returns the request headers and body
+    fn stand_in(status: &'static str) -> (String, thread::JoinHandle<(Vec<String>, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end().to_string();
+                if line.is_empty() {
+                    break
+                }
+                headers.push(line.to_lowercase());
+            }
+            let length: usize =
+                headers
+                    .iter()
+                    .find_map(|h| h.strip_prefix("content-length: "))
+                    .map_or(0, |n| n.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(&stream, "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+            (headers, String::from_utf8(body).unwrap())
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn post_sends_body_with_signature() {
+        let (url, handle) = stand_in("200 OK");
+        let body = "The quick brown fox jumps over the lazy dog";
+        post(&url, b"key", body).unwrap();
+
+        let (headers, received) = handle.join().unwrap();
+        assert_eq!(received, body);
+        assert!(headers.contains(&"post /hook http/1.1".to_string()), "{headers:?}");
+        assert!(headers.contains(&"content-type: application/json".to_string()), "{headers:?}");
+        // known HMAC-SHA256 of the body with the key "key"
+        assert!(
+            headers.contains(&"x-signature: sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8".to_string()),
+            "{headers:?}"
+        );
+    }
+
+    #[test]
+    fn post_fails_on_error_status() {
+        let (url, handle) = stand_in("500 Internal Server Error");
+        assert!(post(&url, b"key", "{}").is_err());
+        handle.join().unwrap();
+    }
+}
@@ -110,3 +110,62 @@ $(document).ready(function() {
         hide_empty();
     });
 });
+
+// Комментарий к клетке: двойной щелчок по клетке с отметкой
+$(document).ready(function() {
+    $('input.mark').parent('td').on('dblclick', function() {
+        const hidden = $(this).find('input[name^="C"]');
+        const comment = prompt('Комментарий к отметке:', hidden.val());
+        if (comment === null)
+            return;
+        const text = comment.replace(/\s+/g, ' ').trim();
+        hidden.val(text);
+        $(this).attr('title', text);
+        $(this).toggleClass('commented', text > '');
+    });
+});
+
+// Добавление строки для нового ученика
+$(document).ready(function() {
+    $('#add-row').on('click', function() {
+        const rows = $('table tbody tr').has('input[name^="IN-"]');
+        const hidden = rows.filter(':hidden');
+        if (hidden.length > 0) {
+            hidden.first().show();
+            return;
+        }
+        const last = rows.last();
+        if (last.length === 0)
+            return;
+
+        const last_id = last.find('input[name^="IN-"]').attr('name').substring(2);
+        const id = '-' + String(-parseInt(last_id, 10) + 1).padStart(4, '0');
+
+        const row = last.clone(true);
+        row.find('[name]').each(function() {
+            $(this).attr('name', $(this).attr('name').replace(last_id + 'D', id + 'D').replace(/^(I?N)-\d+$/, '$1' + id));
+        });
+        row.find('input[list^="N-"]').attr('list', 'N' + id);
+        row.find('datalist').attr('id', 'N' + id).empty();
+        row.find('input').val('');
+        row.find('td').not('.numcol, .idcol, .namecol').attr('title', '').removeClass('commented');
+        row.find('td.numcol').text($('table tbody tr').has('input[name^="IN"]').length + 1);
+        last.after(row);
+        row.show();
+    });
+});
+
+// Ученик выбыл из группы: клетки после даты выбытия недоступны
+$(document).ready(function() {
+    $('input.left').on('change', function() {
+        const id = $(this).attr('name').substring(1);
+        const left = $(this).val();
+        $(this).parent('td').toggleClass('left', left > '');
+        $('input[name^="S' + id + 'D"]').each(function() {
+            const after = left > '' && $(this).attr('name').substring(id.length + 2) > left;
+            $(this).prop('disabled', after);
+            $(this).siblings('input[name^="C"]').prop('disabled', after);
+            $(this).parent('td').toggleClass('left', after);
+        });
+    });
+});